@@ -0,0 +1,32 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use robotic_ai_prypiat::benchmark::{configurations, generate, run_on, suite};
+
+// keep the benches short, the `score` binary does the long runs
+const BENCH_TICKS: usize = 50;
+
+fn strategies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("strategies");
+    group.sample_size(10);
+    for spec in suite().iter().filter(|spec| spec.size <= 128) {
+        // generated once per world, every run gets a copy made outside the
+        // measurement
+        let world = generate(spec).unwrap();
+        for config in configurations() {
+            group.bench_with_input(
+                BenchmarkId::new(config.name, spec.to_string()),
+                spec,
+                |b, spec| {
+                    b.iter_batched(
+                        || world.clone(),
+                        |mut world| run_on(&mut world, spec, &config, BENCH_TICKS),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, strategies);
+criterion_main!(benches);
@@ -0,0 +1,436 @@
+//! Fixed suite of generated worlds used to compare Scrapbot configurations.
+//!
+//! Shared by the criterion benches (`benches/strategies.rs`) and the `score`
+//! binary, so both measure exactly the same runs.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use oxagworldgenerator::world_generator::{
+    content_options::OxAgContentOption, presets::content_presets::OxAgContentPresets,
+    world_generator_builder::OxAgWorldGeneratorBuilder, OxAgWorldGenerator,
+};
+use robotics_lib::runner::Runner;
+use robotics_lib::utils::LibError;
+use robotics_lib::world::environmental_conditions::EnvironmentalConditions;
+use robotics_lib::world::tile::{Content, Tile};
+use robotics_lib::world::world_generator::Generator;
+
+use crate::robot::{
+    energy_reserve::EnergyReserve, metrics::Metrics, resources::ResourceTable,
+    scheduling::SchedulingPolicy, sensing::SensingPolicy, Scrapbot,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GarbageDensity {
+    Sparse,
+    Normal,
+    Dense,
+}
+
+impl GarbageDensity {
+    fn spawn_percentage(&self) -> f64 {
+        match self {
+            GarbageDensity::Sparse => 0.002,
+            GarbageDensity::Normal => 0.01,
+            GarbageDensity::Dense => 0.04,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WorldSpec {
+    pub size: usize,
+    pub seed: u64,
+    pub density: GarbageDensity,
+    /// Very few bins, most garbage is far from any of them.
+    pub scarce_bins: bool,
+}
+
+impl fmt::Display for WorldSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}-{:?}-{}",
+            self.size, self.size, self.density, self.seed
+        )?;
+        if self.scarce_bins {
+            write!(f, "-scarce-bins")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a benchmark run couldn't complete.
+#[derive(Debug)]
+pub enum BenchmarkError {
+    /// The world generator refused the spec.
+    Generation(String),
+    /// The runner failed while building or ticking.
+    Run(LibError),
+}
+
+impl From<LibError> for BenchmarkError {
+    fn from(err: LibError) -> Self {
+        BenchmarkError::Run(err)
+    }
+}
+
+/// A named way of building the bot.
+pub struct BotConfig {
+    pub name: &'static str,
+    pub build: fn() -> Scrapbot,
+}
+
+/// Outcome of running one configuration on one world.
+#[derive(Debug, Clone)]
+pub struct Score {
+    pub world: String,
+    pub config: &'static str,
+    pub ticks: usize,
+    pub garbage_collected: usize,
+    pub garbage_delivered: usize,
+    pub energy_consumed: usize,
+    /// Garbage delivered per 1000 energy units.
+    pub energy_efficiency: f64,
+    /// Share of the map in the known map, from 0 to 1.
+    pub coverage: f64,
+    /// Tiles discovered per LSSF scan.
+    pub scan_yield: f64,
+}
+
+/// Committed scores the `score` binary compares every run with, one
+/// `world,config,ticks,delivered,efficiency,coverage` line per run.
+pub const BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/score_baseline.csv");
+
+/// Share of a baseline value a run may lose before it counts as a
+/// regression, the runs aren't exactly reproducible across machines.
+pub const BASELINE_TOLERANCE: f64 = 0.05;
+
+/// The numbers of a [`Score`] that must not go down.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineScore {
+    pub ticks: usize,
+    pub garbage_delivered: usize,
+    pub energy_efficiency: f64,
+    pub coverage: f64,
+}
+
+impl Score {
+    /// This score as a line of the baseline file.
+    pub fn baseline_line(&self) -> String {
+        format!(
+            "{},{},{},{},{:.3},{:.4}",
+            self.world,
+            self.config,
+            self.ticks,
+            self.garbage_delivered,
+            self.energy_efficiency,
+            self.coverage
+        )
+    }
+
+    /// What got worse than `baseline` beyond the tolerance, empty if
+    /// nothing did. Runs of a different length aren't compared.
+    pub fn regressions(&self, baseline: &BaselineScore) -> Vec<String> {
+        if self.ticks != baseline.ticks {
+            return vec![];
+        }
+        let mut regressions = vec![];
+        let mut check = |name: &str, value: f64, expected: f64| {
+            if value < expected * (1.0 - BASELINE_TOLERANCE) {
+                regressions.push(format!("{} {:.3} < {:.3}", name, value, expected));
+            }
+        };
+        check(
+            "delivered",
+            self.garbage_delivered as f64,
+            baseline.garbage_delivered as f64,
+        );
+        check(
+            "efficiency",
+            self.energy_efficiency,
+            baseline.energy_efficiency,
+        );
+        check("coverage", self.coverage, baseline.coverage);
+        regressions
+    }
+}
+
+/// Reads the baseline file, keyed by world and configuration. Empty lines,
+/// `#` comments and malformed lines are skipped.
+pub fn parse_baseline(text: &str) -> HashMap<(String, String), BaselineScore> {
+    let mut baseline = HashMap::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        if let [world, config, ticks, delivered, efficiency, coverage] = fields[..] {
+            if let (Ok(ticks), Ok(garbage_delivered), Ok(energy_efficiency), Ok(coverage)) = (
+                ticks.parse(),
+                delivered.parse(),
+                efficiency.parse(),
+                coverage.parse(),
+            ) {
+                baseline.insert(
+                    (world.to_string(), config.to_string()),
+                    BaselineScore {
+                        ticks,
+                        garbage_delivered,
+                        energy_efficiency,
+                        coverage,
+                    },
+                );
+            }
+        }
+    }
+    baseline
+}
+
+/// The fixed world suite, every size with every density, plus a world with
+/// far away bins to check the energy reserve.
+pub fn suite() -> Vec<WorldSpec> {
+    let mut worlds = vec![];
+    for size in [64, 128, 256] {
+        for density in [
+            GarbageDensity::Sparse,
+            GarbageDensity::Normal,
+            GarbageDensity::Dense,
+        ] {
+            worlds.push(WorldSpec {
+                size,
+                seed: 42,
+                density,
+                scarce_bins: false,
+            });
+        }
+    }
+    worlds.push(WorldSpec {
+        size: 128,
+        seed: 42,
+        density: GarbageDensity::Normal,
+        scarce_bins: true,
+    });
+    worlds
+}
+
+pub fn configurations() -> Vec<BotConfig> {
+    vec![
+        BotConfig {
+            name: "default",
+            build: Scrapbot::headless,
+        },
+        BotConfig {
+            name: "no_scheduling",
+            build: || {
+                let mut bot = Scrapbot::headless();
+                bot.scheduling = SchedulingPolicy::disabled();
+                bot
+            },
+        },
+        BotConfig {
+            name: "no_reserve",
+            build: || {
+                let mut bot = Scrapbot::headless();
+                bot.reserve = EnergyReserve::disabled();
+                bot
+            },
+        },
+        BotConfig {
+            name: "fixed_scan_radius",
+            build: || {
+                let mut bot = Scrapbot::headless();
+                bot.sensing = SensingPolicy::disabled();
+                bot
+            },
+        },
+        BotConfig {
+            name: "all_resources",
+            build: || {
+                let mut bot = Scrapbot::headless();
+                bot.resources = ResourceTable::all_resources();
+                bot
+            },
+        },
+    ]
+}
+
+/// Builds the generator of the world of `spec`.
+pub fn generator(spec: &WorldSpec) -> Result<OxAgWorldGenerator, BenchmarkError> {
+    let mut builder = OxAgWorldGeneratorBuilder::new()
+        .set_seed(spec.seed)
+        .set_size(spec.size)
+        .set_content_options_from_preset(OxAgContentPresets::Default)
+        .alter_content_option(
+            Content::Garbage(0),
+            OxAgContentOption {
+                min_spawn_number: 1,
+                max_radius: 2,
+                with_max_spawn_number: false,
+                max_spawn_number: 0,
+                percentage: spec.density.spawn_percentage(),
+            },
+        );
+    if spec.scarce_bins {
+        builder = builder.alter_content_option(
+            Content::Bin(0..0),
+            OxAgContentOption {
+                min_spawn_number: 1,
+                max_radius: 0,
+                with_max_spawn_number: true,
+                max_spawn_number: 2,
+                percentage: 0.0001,
+            },
+        );
+    }
+    builder
+        .build()
+        .map_err(|err| BenchmarkError::Generation(format!("{:?}", err)))
+}
+
+/// A world generated once, replayed to the runner without generating it
+/// again. Each copy can be handed to one runner.
+#[derive(Clone)]
+pub struct GeneratedWorld {
+    tiles: Vec<Vec<Tile>>,
+    spawn: (usize, usize),
+    conditions: EnvironmentalConditions,
+    max_score: f32,
+    score_table: Option<HashMap<Content, f32>>,
+}
+
+impl Generator for GeneratedWorld {
+    fn gen(
+        &mut self,
+    ) -> (
+        Vec<Vec<Tile>>,
+        (usize, usize),
+        EnvironmentalConditions,
+        f32,
+        Option<HashMap<Content, f32>>,
+    ) {
+        // the tiles are moved out, copying them is the caller's business
+        (
+            std::mem::take(&mut self.tiles),
+            self.spawn,
+            self.conditions.clone(),
+            self.max_score,
+            self.score_table.clone(),
+        )
+    }
+}
+
+/// Generates the world of `spec`.
+pub fn generate(spec: &WorldSpec) -> Result<GeneratedWorld, BenchmarkError> {
+    let (tiles, spawn, conditions, max_score, score_table) = generator(spec)?.gen();
+    Ok(GeneratedWorld {
+        tiles,
+        spawn,
+        conditions,
+        max_score,
+        score_table,
+    })
+}
+
+/// Generates the world of `spec` and runs `config` on it for `ticks` ticks.
+pub fn run(spec: &WorldSpec, config: &BotConfig, ticks: usize) -> Result<Score, BenchmarkError> {
+    let mut world = generate(spec)?;
+    run_on(&mut world, spec, config, ticks)
+}
+
+/// Runs `config` for `ticks` ticks on a world from [`generate`], or any
+/// other generator.
+pub fn run_on(
+    generator: &mut impl Generator,
+    spec: &WorldSpec,
+    config: &BotConfig,
+    ticks: usize,
+) -> Result<Score, BenchmarkError> {
+    let metrics = Arc::new(Mutex::new(Metrics::new()));
+    let bot = (config.build)().with_metrics_sink(Arc::clone(&metrics));
+    let mut runner = Runner::new(Box::new(bot), generator)?;
+
+    for _ in 0..ticks {
+        runner.game_tick()?;
+    }
+
+    let metrics = metrics.lock().unwrap();
+    let energy_efficiency = match metrics.energy_consumed {
+        0 => 0.0,
+        energy => metrics.garbage_deposited as f64 * 1000.0 / energy as f64,
+    };
+
+    Ok(Score {
+        world: spec.to_string(),
+        config: config.name,
+        ticks: metrics.ticks,
+        garbage_collected: metrics.garbage_collected,
+        garbage_delivered: metrics.garbage_deposited,
+        energy_consumed: metrics.energy_consumed,
+        energy_efficiency,
+        coverage: metrics.tiles_discovered as f64 / (spec.size * spec.size) as f64,
+        scan_yield: metrics.scan_yield(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(delivered: usize, efficiency: f64, coverage: f64) -> Score {
+        Score {
+            world: "64x64-Normal-42".to_string(),
+            config: "default",
+            ticks: 500,
+            garbage_collected: delivered,
+            garbage_delivered: delivered,
+            energy_consumed: 1000,
+            energy_efficiency: efficiency,
+            coverage,
+            scan_yield: 0.0,
+        }
+    }
+
+    #[test]
+    fn baseline_lines_read_back() {
+        let text = format!(
+            "# world,config,ticks,delivered,efficiency,coverage\n{}\nbroken,line\n",
+            score(40, 12.5, 0.75).baseline_line()
+        );
+        let baseline = parse_baseline(&text);
+        assert_eq!(baseline.len(), 1);
+        assert_eq!(
+            baseline[&("64x64-Normal-42".to_string(), "default".to_string())],
+            BaselineScore {
+                ticks: 500,
+                garbage_delivered: 40,
+                energy_efficiency: 12.5,
+                coverage: 0.75,
+            }
+        );
+    }
+
+    #[test]
+    fn only_drops_beyond_the_tolerance_are_regressions() {
+        let baseline = BaselineScore {
+            ticks: 500,
+            garbage_delivered: 100,
+            energy_efficiency: 10.0,
+            coverage: 0.5,
+        };
+        assert!(score(96, 10.0, 0.6).regressions(&baseline).is_empty());
+
+        let regressions = score(90, 10.0, 0.4).regressions(&baseline);
+        assert_eq!(regressions.len(), 2);
+        assert!(regressions[0].starts_with("delivered"));
+        assert!(regressions[1].starts_with("coverage"));
+
+        let shorter = BaselineScore {
+            ticks: 100,
+            ..baseline
+        };
+        assert!(score(0, 0.0, 0.0).regressions(&shorter).is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+//! Runs every configuration on the benchmark world suite, prints the scores
+//! and compares them with the committed baseline. Exits with an error if a
+//! run scored below its baseline.
+//!
+//! Usage: `cargo run --release --bin score [ticks] [--write-baseline]`
+//!
+//! `--write-baseline` replaces the baseline with the scores of this run.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use robotic_ai_prypiat::benchmark::{
+    configurations, parse_baseline, run, suite, BASELINE_PATH, BASELINE_TOLERANCE,
+};
+
+const DEFAULT_TICKS: usize = 500;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let write_baseline = args.iter().any(|arg| arg == "--write-baseline");
+    let ticks = args
+        .iter()
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_TICKS);
+    let baseline = parse_baseline(&fs::read_to_string(BASELINE_PATH).unwrap_or_default());
+
+    println!(
+        "{:<30} {:<18} {:>10} {:>10} {:>10} {:>12} {:>9} {:>11}",
+        "world",
+        "config",
+        "collected",
+        "delivered",
+        "energy",
+        "garbage/1kE",
+        "coverage",
+        "tiles/scan"
+    );
+    let mut lines = vec!["# world,config,ticks,delivered,efficiency,coverage".to_string()];
+    let mut regressions = vec![];
+    for spec in suite() {
+        for config in configurations() {
+            match run(&spec, &config, ticks) {
+                Ok(score) => {
+                    println!(
+                        "{:<30} {:<18} {:>10} {:>10} {:>10} {:>12.2} {:>8.1}% {:>11.1}",
+                        score.world,
+                        score.config,
+                        score.garbage_collected,
+                        score.garbage_delivered,
+                        score.energy_consumed,
+                        score.energy_efficiency,
+                        score.coverage * 100.0,
+                        score.scan_yield
+                    );
+                    lines.push(score.baseline_line());
+                    match baseline.get(&(score.world.clone(), score.config.to_string())) {
+                        Some(expected) => {
+                            for regression in score.regressions(expected) {
+                                regressions
+                                    .push(format!("{} {}: {}", spec, config.name, regression));
+                            }
+                        }
+                        None => eprintln!("{} {}: no baseline", spec, config.name),
+                    }
+                }
+                Err(err) => eprintln!("{} {}: {:?}", spec, config.name, err),
+            }
+        }
+    }
+
+    if write_baseline {
+        lines.push(String::new());
+        fs::write(BASELINE_PATH, lines.join("\n")).expect("can't write the baseline");
+        println!("baseline written to {}", BASELINE_PATH);
+        return;
+    }
+    if !regressions.is_empty() {
+        eprintln!(
+            "scores below the baseline (tolerance {:.0}%):",
+            BASELINE_TOLERANCE * 100.0
+        );
+        for regression in &regressions {
+            eprintln!("  {}", regression);
+        }
+        process::exit(1);
+    }
+}
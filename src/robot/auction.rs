@@ -0,0 +1,393 @@
+//! Contract-net style task allocation between the bots of a [`Fleet`].
+//!
+//! The bot that finds a new pile announces it, every member bids its
+//! estimated path cost and spare backpack capacity on its next tick and, once
+//! everybody has bid, the cheapest bidder gets the claim on the pile. Bots
+//! that stopped ticking don't hold an auction forever: the announcer closes it
+//! with the bids it got after [`AUCTION_TIMEOUT`] of its ticks, and the
+//! winner's claim expires like any other if it never gets there. Every closed
+//! auction is appended to the assignment log. A pile nobody could take isn't
+//! auctioned again, the first bot that claims it gets it.
+//!
+//! [`Fleet`]: crate::robot::fleet::Fleet
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{
+    cost_model::reach_cost,
+    fleet::{BotId, FleetMember},
+    grid::GridPos,
+    Scrapbot,
+};
+
+/// Ticks of the announcer after which an auction closes with the bids it got.
+pub const AUCTION_TIMEOUT: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bid {
+    pub bot: BotId,
+    /// Estimated energy to reach the pile, `None` if we can't reach it.
+    pub path_cost: Option<usize>,
+    pub spare_capacity: usize,
+}
+
+impl Bid {
+    fn is_valid(&self) -> bool {
+        self.path_cost.is_some() && self.spare_capacity > 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Auction {
+    pub announced_by: BotId,
+    /// Tick of the announcer after which the auction closes anyway.
+    pub deadline: usize,
+    pub bids: HashMap<BotId, Bid>,
+}
+
+impl Auction {
+    /// Cheapest valid bid, ties go to the bigger spare capacity and then to
+    /// the lowest id so every bot agrees on the winner.
+    pub fn winner(&self) -> Option<Bid> {
+        self.bids
+            .values()
+            .filter(|bid| bid.is_valid())
+            .min_by_key(|bid| (bid.path_cost, Reverse(bid.spare_capacity), bid.bot))
+            .copied()
+    }
+}
+
+/// One closed auction, `winner` is `None` when nobody could take the pile.
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub coords: GridPos,
+    pub winner: Option<BotId>,
+    pub bids: Vec<Bid>,
+}
+
+impl FleetMember {
+    /// Opens an auction for `coords` at our `tick` unless there's already one,
+    /// the pile is already assigned or a previous auction found no taker.
+    pub fn announce(&self, coords: GridPos, tick: usize) {
+        let id = self.get_id();
+        let mut board = self.board();
+        if board.get_claims().contains_key(&coords)
+            || board.auctions.contains_key(&coords)
+            || board.unassigned.contains(&coords)
+        {
+            return;
+        }
+        board.auctions.insert(
+            coords,
+            Auction {
+                announced_by: id,
+                deadline: tick + AUCTION_TIMEOUT,
+                bids: HashMap::new(),
+            },
+        );
+    }
+
+    /// Open auctions this bot hasn't bid on yet.
+    pub fn pending_auctions(&self) -> Vec<GridPos> {
+        let id = self.get_id();
+        self.board()
+            .auctions
+            .iter()
+            .filter(|(_, auction)| !auction.bids.contains_key(&id))
+            .map(|(coords, _)| *coords)
+            .collect()
+    }
+
+    pub fn bid(&self, coords: GridPos, bid: Bid) {
+        if let Some(auction) = self.board().auctions.get_mut(&coords) {
+            auction.bids.insert(bid.bot, bid);
+        }
+    }
+
+    /// Closes every auction all members have bid on, and the ones we
+    /// announced that are past their deadline at our `tick`, giving the claim
+    /// to the winner and logging the assignment. Claims whose owner didn't
+    /// get to them in time are dropped.
+    pub fn close_auctions(&self, tick: usize) {
+        let id = self.get_id();
+        let mut board = self.board();
+        board.advance_clock(tick);
+        let members = board.member_count();
+        let complete: Vec<GridPos> = board
+            .auctions
+            .iter()
+            .filter(|(_, auction)| {
+                auction.bids.len() >= members
+                    || (auction.announced_by == id && tick >= auction.deadline)
+            })
+            .map(|(coords, _)| *coords)
+            .collect();
+
+        for coords in complete {
+            let auction = board.auctions.remove(&coords).unwrap();
+            let winner = auction.winner().map(|bid| bid.bot);
+            match winner {
+                Some(winner) => {
+                    board.insert_claim(coords, winner);
+                }
+                None => {
+                    board.unassigned.insert(coords);
+                }
+            }
+            let mut bids: Vec<Bid> = auction.bids.into_values().collect();
+            bids.sort_by_key(|bid| bid.bot);
+            board.assignments.push(Assignment {
+                coords,
+                winner,
+                bids,
+            });
+        }
+    }
+
+    pub fn is_under_auction(&self, coords: GridPos) -> bool {
+        self.board().auctions.contains_key(&coords)
+    }
+}
+
+impl Scrapbot {
+    /// Bids on every open auction and closes the complete ones. The costs of
+    /// every pile come from a single Dijkstra.
+    pub(crate) fn run_auctions(&mut self, world: &World) {
+        let fleet = match &self.fleet {
+            Some(fleet) => fleet.clone(),
+            None => return,
+        };
+
+        let pending = fleet.pending_auctions();
+        if !pending.is_empty() {
+            let known_map = self.known_map(world);
+            let costs = self
+                .cost_model()
+                .costs_from(&known_map, self.position(), true);
+
+            for coords in pending {
+                // a pile that failed us recently isn't worth a bid, say so
+                // instead of keeping the auction waiting
+                if self.blacklist.is_banned(coords, self.tick) {
+                    fleet.bid(
+                        coords,
+                        Bid {
+                            bot: fleet.get_id(),
+                            path_cost: None,
+                            spare_capacity: 0,
+                        },
+                    );
+                    continue;
+                }
+                // room for whatever lies on the pile
+                let content = fleet
+                    .board()
+                    .get_piles()
+                    .get(&coords)
+                    .cloned()
+                    .unwrap_or(Content::Garbage(0));
+                let spare_capacity = self.takeable_quantity(&content);
+                fleet.bid(
+                    coords,
+                    Bid {
+                        bot: fleet.get_id(),
+                        path_cost: reach_cost(&costs, &known_map, coords),
+                        spare_capacity,
+                    },
+                );
+            }
+        }
+
+        fleet.close_auctions(self.tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::fleet::{Fleet, CLAIM_TIMEOUT};
+
+    fn bid(bot: BotId, path_cost: Option<usize>, spare_capacity: usize) -> Bid {
+        Bid {
+            bot,
+            path_cost,
+            spare_capacity,
+        }
+    }
+
+    #[test]
+    fn cheapest_valid_bid_wins() {
+        let mut auction = Auction::default();
+        auction.bids.insert(0, bid(0, Some(12), 5));
+        auction.bids.insert(1, bid(1, Some(7), 0));
+        auction.bids.insert(2, bid(2, None, 20));
+        auction.bids.insert(3, bid(3, Some(9), 5));
+        auction.bids.insert(4, bid(4, Some(9), 8));
+        assert_eq!(auction.winner().map(|bid| bid.bot), Some(4));
+    }
+
+    #[test]
+    fn complete_auction_is_logged_and_claimed() {
+        let fleet = Fleet::new();
+        let first = fleet.join();
+        let second = fleet.join();
+        let pile = GridPos::new(3, 4);
+
+        first.announce(pile, 0);
+        assert!(first.is_under_auction(pile));
+        // nobody can take it while it's being auctioned
+        assert!(!second.claim(pile));
+
+        first.bid(pile, bid(0, Some(10), 5));
+        first.close_auctions(1);
+        assert!(first.is_under_auction(pile));
+
+        second.bid(pile, bid(1, Some(4), 5));
+        second.close_auctions(1);
+
+        let log = fleet.assignment_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].coords, pile);
+        assert_eq!(log[0].winner, Some(1));
+        assert_eq!(log[0].bids, vec![bid(0, Some(10), 5), bid(1, Some(4), 5)]);
+        assert_eq!(fleet.board().get_claims().get(&pile), Some(&1));
+        assert!(!first.claim(pile));
+        assert!(second.claim(pile));
+    }
+
+    #[test]
+    fn announcer_closes_after_the_timeout() {
+        let fleet = Fleet::new();
+        let first = fleet.join();
+        let second = fleet.join();
+        let pile = GridPos::new(1, 1);
+
+        first.announce(pile, 10);
+        first.bid(pile, bid(0, Some(3), 2));
+
+        // the other bot doesn't bid, only the announcer's clock counts
+        second.close_auctions(10 + AUCTION_TIMEOUT);
+        first.close_auctions(10 + AUCTION_TIMEOUT - 1);
+        assert!(first.is_under_auction(pile));
+
+        first.close_auctions(10 + AUCTION_TIMEOUT);
+        assert!(!first.is_under_auction(pile));
+        let log = fleet.assignment_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].winner, Some(0));
+        assert_eq!(log[0].bids, vec![bid(0, Some(3), 2)]);
+    }
+
+    #[test]
+    fn unreachable_pile_is_logged_without_winner() {
+        let fleet = Fleet::new();
+        let member = fleet.join();
+        let pile = GridPos::new(0, 2);
+
+        member.announce(pile, 0);
+        member.bid(pile, bid(0, None, 10));
+        member.close_auctions(0);
+
+        let log = fleet.assignment_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].winner, None);
+        assert!(fleet.board().get_claims().is_empty());
+    }
+
+    #[test]
+    fn pile_without_taker_is_not_auctioned_again() {
+        let fleet = Fleet::new();
+        let member = fleet.join();
+        let pile = GridPos::new(0, 2);
+
+        member.announce(pile, 0);
+        member.bid(pile, bid(0, None, 10));
+        member.close_auctions(0);
+        for tick in 1..10 {
+            member.announce(pile, tick);
+            member.close_auctions(tick);
+        }
+        assert!(!member.is_under_auction(pile));
+        assert_eq!(fleet.assignment_log().len(), 1);
+
+        // whoever gets there first can still take it
+        assert!(member.claim(pile));
+        // and once it's gone it can be auctioned again if it comes back
+        member.publish_pile(pile, Content::Garbage(0));
+        assert!(fleet.board().get_unassigned().is_empty());
+    }
+
+    #[test]
+    fn claimed_pile_is_not_auctioned_again() {
+        let fleet = Fleet::new();
+        let member = fleet.join();
+        let pile = GridPos::new(5, 5);
+
+        assert!(member.claim(pile));
+        member.announce(pile, 0);
+        assert!(!member.is_under_auction(pile));
+        assert!(member.pending_auctions().is_empty());
+    }
+
+    #[test]
+    fn winner_that_never_arrives_loses_the_claim() {
+        let fleet = Fleet::new();
+        let first = fleet.join();
+        let second = fleet.join();
+        let pile = GridPos::new(2, 6);
+
+        first.announce(pile, 0);
+        first.bid(pile, bid(0, Some(9), 5));
+        second.bid(pile, bid(1, Some(2), 5));
+        first.close_auctions(0);
+        assert_eq!(fleet.board().get_claims().get(&pile), Some(&1));
+
+        // the winner stops ticking, the other bot's clock keeps going
+        first.close_auctions(CLAIM_TIMEOUT - 1);
+        assert!(first.is_claimed_by_other(pile));
+        first.close_auctions(CLAIM_TIMEOUT);
+        assert!(!first.is_claimed_by_other(pile));
+        assert!(first.claim(pile));
+    }
+
+    #[test]
+    fn renewed_claim_outlives_the_first_deadline() {
+        let fleet = Fleet::new();
+        let member = fleet.join();
+        let pile = GridPos::new(4, 1);
+
+        assert!(member.claim(pile));
+        member.close_auctions(CLAIM_TIMEOUT - 1);
+        assert!(member.claim(pile));
+        member.close_auctions(CLAIM_TIMEOUT);
+        assert_eq!(fleet.board().get_claims().get(&pile), Some(&0));
+    }
+
+    #[test]
+    fn bot_leaving_frees_its_claims_and_its_bid() {
+        let fleet = Fleet::new();
+        let first = fleet.join();
+        let second = fleet.join();
+        let claimed = GridPos::new(0, 0);
+        let pile = GridPos::new(3, 3);
+
+        assert!(second.claim(claimed));
+        first.announce(pile, 0);
+        first.bid(pile, bid(0, Some(5), 1));
+        first.close_auctions(0);
+        assert!(first.is_under_auction(pile));
+
+        second.leave();
+        assert_eq!(fleet.board().member_count(), 1);
+        assert!(first.claim(claimed));
+        // nobody else is left to bid
+        first.close_auctions(1);
+        assert_eq!(fleet.board().get_claims().get(&pile), Some(&0));
+        // ids aren't reused
+        assert_eq!(fleet.join().get_id(), 2);
+    }
+}
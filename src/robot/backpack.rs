@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::mem::{discriminant, Discriminant};
+
+use robotics_lib::world::tile::Content;
+
+use crate::robot::Scrapbot;
+
+/// Per-content quotas and space reservations on top of the library backpack.
+///
+/// Quotas cap how many items of a kind we're willing to carry, reservations
+/// keep room for what the current plan is going to pick up so that other
+/// collections don't steal it.
+#[derive(Debug, Clone, Default)]
+pub struct BackpackManager {
+    quotas: HashMap<Discriminant<Content>, usize>,
+    reservations: HashMap<Discriminant<Content>, usize>,
+}
+
+impl BackpackManager {
+    pub fn new() -> BackpackManager {
+        BackpackManager::default()
+    }
+
+    pub fn with_quota(mut self, content: &Content, quota: usize) -> BackpackManager {
+        self.set_quota(content, quota);
+        self
+    }
+
+    pub fn set_quota(&mut self, content: &Content, quota: usize) {
+        self.quotas.insert(discriminant(content), quota);
+    }
+
+    pub fn clear_quota(&mut self, content: &Content) {
+        self.quotas.remove(&discriminant(content));
+    }
+
+    pub fn get_quota(&self, content: &Content) -> Option<usize> {
+        self.quotas.get(&discriminant(content)).copied()
+    }
+
+    pub fn reserve(&mut self, content: &Content, quantity: usize) {
+        *self.reservations.entry(discriminant(content)).or_insert(0) += quantity;
+    }
+
+    pub fn release(&mut self, content: &Content, quantity: usize) {
+        if let Some(reserved) = self.reservations.get_mut(&discriminant(content)) {
+            *reserved = reserved.saturating_sub(quantity);
+        }
+    }
+
+    pub fn clear_reservations(&mut self) {
+        self.reservations.clear();
+    }
+
+    pub fn reserved(&self, content: &Content) -> usize {
+        *self.reservations.get(&discriminant(content)).unwrap_or(&0)
+    }
+
+    pub fn total_reserved(&self) -> usize {
+        self.reservations.values().sum()
+    }
+}
+
+impl Scrapbot {
+    pub fn backpack_capacity(&self) -> usize {
+        self.robot.backpack.get_size()
+    }
+
+    /// Space left in the backpack, ignoring reservations.
+    pub fn free_backpack_space(&self) -> usize {
+        let used_space: usize = self.robot.backpack.get_contents().values().sum();
+        self.backpack_capacity().saturating_sub(used_space)
+    }
+
+    /// Largest quantity of `content` we can take right now without breaking
+    /// its quota or eating reserved space, same kind included: release a
+    /// reservation before collecting what it was made for.
+    pub fn takeable_quantity(&self, content: &Content) -> usize {
+        let space = self
+            .free_backpack_space()
+            .saturating_sub(self.backpack_manager.total_reserved());
+
+        match self.backpack_manager.get_quota(content) {
+            Some(quota) => space.min(
+                quota
+                    .saturating_sub(self.carried_quantity(content))
+                    .saturating_sub(self.backpack_manager.reserved(content)),
+            ),
+            None => space,
+        }
+    }
+
+    /// Can the robot take `quantity` items of `content`?
+    pub fn can_take(&self, content: &Content, quantity: usize) -> bool {
+        quantity <= self.takeable_quantity(content)
+    }
+
+    /// Share of the backpack in use, from 0 to 1.
+    pub fn backpack_fill_ratio(&self) -> f32 {
+        match self.backpack_capacity() {
+            0 => 1.0,
+            capacity => (capacity - self.free_backpack_space()) as f32 / capacity as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::test_world::test_bot;
+
+    #[test]
+    fn releases_never_go_below_zero() {
+        let mut manager = BackpackManager::new();
+        manager.reserve(&Content::Rock(0), 3);
+        manager.reserve(&Content::Rock(4), 2);
+        manager.reserve(&Content::Tree(0), 1);
+        assert_eq!(manager.reserved(&Content::Rock(0)), 5);
+        assert_eq!(manager.total_reserved(), 6);
+
+        manager.release(&Content::Rock(0), 10);
+        manager.release(&Content::Fish(0), 1);
+        assert_eq!(manager.reserved(&Content::Rock(0)), 0);
+        assert_eq!(manager.total_reserved(), 1);
+    }
+
+    #[test]
+    fn quota_caps_only_its_own_kind() {
+        let mut bot = test_bot();
+        let capacity = bot.backpack_capacity();
+        bot.backpack_manager.set_quota(&Content::Water(0), 3);
+
+        assert_eq!(bot.takeable_quantity(&Content::Water(2)), 3);
+        assert_eq!(bot.takeable_quantity(&Content::Rock(0)), capacity);
+        assert!(bot.can_take(&Content::Water(0), 3));
+        assert!(!bot.can_take(&Content::Water(0), 4));
+
+        bot.backpack_manager.clear_quota(&Content::Water(0));
+        assert_eq!(bot.takeable_quantity(&Content::Water(0)), capacity);
+    }
+
+    #[test]
+    fn reservations_take_space_from_every_kind() {
+        let mut bot = test_bot();
+        let capacity = bot.backpack_capacity();
+        bot.backpack_manager.reserve(&Content::Rock(0), 5);
+
+        assert_eq!(bot.takeable_quantity(&Content::Garbage(0)), capacity - 5);
+        // the rocks too: the reservation is released before collecting them
+        assert_eq!(bot.takeable_quantity(&Content::Rock(0)), capacity - 5);
+
+        bot.backpack_manager.release(&Content::Rock(0), 5);
+        assert_eq!(bot.takeable_quantity(&Content::Garbage(0)), capacity);
+    }
+
+    #[test]
+    fn same_kind_reservation_counts_against_the_quota() {
+        let mut bot = test_bot();
+        bot.backpack_manager.set_quota(&Content::Water(0), 4);
+        bot.backpack_manager.reserve(&Content::Water(0), 3);
+        assert_eq!(bot.takeable_quantity(&Content::Water(0)), 1);
+
+        bot.backpack_manager.reserve(&Content::Water(0), 3);
+        assert_eq!(bot.takeable_quantity(&Content::Water(0)), 0);
+    }
+}
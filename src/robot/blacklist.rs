@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use crate::robot::grid::GridPos;
+
+/// Why a target coordinate was put on the blacklist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlacklistReason {
+    /// LSSF could not plan a path to the target.
+    Unreachable,
+    /// We reached the target but there was nothing to collect.
+    EmptyTrash,
+    /// We reached the bin but it didn't accept any garbage.
+    FullBin,
+    /// We reached the market but it didn't buy anything we offered.
+    RefusedSale,
+    /// We poured water on the fire but it didn't go out.
+    StillBurning,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlacklistEntry {
+    pub reason: BlacklistReason,
+    pub banned_at: usize,
+    pub expires_at: usize,
+}
+
+/// Targets (garbage piles, bins, exploration points) that failed recently.
+///
+/// Entries live for a number of ticks that depends on the reason, after that
+/// the target can be selected again (a full bin may have been emptied, a new
+/// path may have been discovered, ...).
+pub struct TargetBlacklist {
+    entries: HashMap<GridPos, BlacklistEntry>,
+    ttl: HashMap<BlacklistReason, usize>,
+}
+
+impl Default for TargetBlacklist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TargetBlacklist {
+    pub fn new() -> TargetBlacklist {
+        TargetBlacklist {
+            entries: HashMap::new(),
+            ttl: HashMap::from([
+                (BlacklistReason::Unreachable, 50usize),
+                (BlacklistReason::EmptyTrash, 200usize),
+                (BlacklistReason::FullBin, 100usize),
+                (BlacklistReason::RefusedSale, 100usize),
+                (BlacklistReason::StillBurning, 100usize),
+            ]),
+        }
+    }
+
+    pub fn with_ttl(mut self, reason: BlacklistReason, ticks: usize) -> TargetBlacklist {
+        self.set_ttl(reason, ticks);
+        self
+    }
+
+    pub fn set_ttl(&mut self, reason: BlacklistReason, ticks: usize) {
+        self.ttl.insert(reason, ticks);
+    }
+
+    pub fn get_ttl(&self, reason: BlacklistReason) -> usize {
+        *self.ttl.get(&reason).unwrap_or(&0)
+    }
+
+    /// Bans `coords` starting from `tick`. Banning an already banned target
+    /// refreshes its expiry.
+    pub fn ban(&mut self, coords: GridPos, reason: BlacklistReason, tick: usize) {
+        let expires_at = tick.saturating_add(self.get_ttl(reason));
+        self.entries.insert(
+            coords,
+            BlacklistEntry {
+                reason,
+                banned_at: tick,
+                expires_at,
+            },
+        );
+    }
+
+    pub fn unban(&mut self, coords: GridPos) {
+        self.entries.remove(&coords);
+    }
+
+    pub fn is_banned(&self, coords: GridPos, tick: usize) -> bool {
+        self.get_entry(coords, tick).is_some()
+    }
+
+    pub fn get_entry(&self, coords: GridPos, tick: usize) -> Option<&BlacklistEntry> {
+        self.entries
+            .get(&coords)
+            .filter(|entry| entry.expires_at > tick)
+    }
+
+    /// Drops every entry whose time-to-live is over.
+    pub fn purge_expired(&mut self, tick: usize) {
+        self.entries.retain(|_, entry| entry.expires_at > tick);
+    }
+
+    /// Removes banned coordinates from `coords`, keeping the original order.
+    pub fn retain_allowed(&self, coords: &mut Vec<GridPos>, tick: usize) {
+        coords.retain(|c| !self.is_banned(*c, tick));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PILE: GridPos = GridPos { row: 2, col: 3 };
+
+    #[test]
+    fn ban_expires_after_its_ttl() {
+        let mut blacklist = TargetBlacklist::new().with_ttl(BlacklistReason::Unreachable, 10);
+        blacklist.ban(PILE, BlacklistReason::Unreachable, 5);
+
+        assert!(blacklist.is_banned(PILE, 5));
+        assert!(blacklist.is_banned(PILE, 14));
+        assert!(!blacklist.is_banned(PILE, 15));
+        assert!(!blacklist.is_banned(GridPos::new(0, 0), 5));
+    }
+
+    #[test]
+    fn purge_drops_only_expired_entries() {
+        let mut blacklist = TargetBlacklist::new()
+            .with_ttl(BlacklistReason::Unreachable, 10)
+            .with_ttl(BlacklistReason::FullBin, 30);
+        let bin = GridPos::new(7, 7);
+        blacklist.ban(PILE, BlacklistReason::Unreachable, 0);
+        blacklist.ban(bin, BlacklistReason::FullBin, 0);
+
+        blacklist.purge_expired(9);
+        assert_eq!(blacklist.len(), 2);
+        blacklist.purge_expired(10);
+        assert_eq!(blacklist.len(), 1);
+        assert!(blacklist.get_entry(bin, 10).is_some());
+        blacklist.purge_expired(30);
+        assert!(blacklist.is_empty());
+    }
+
+    #[test]
+    fn banning_again_extends_the_deadline() {
+        let mut blacklist = TargetBlacklist::new().with_ttl(BlacklistReason::EmptyTrash, 10);
+        blacklist.ban(PILE, BlacklistReason::EmptyTrash, 0);
+        blacklist.ban(PILE, BlacklistReason::EmptyTrash, 8);
+
+        let entry = blacklist.get_entry(PILE, 12).unwrap();
+        assert_eq!(entry.banned_at, 8);
+        assert_eq!(entry.expires_at, 18);
+        assert_eq!(blacklist.len(), 1);
+    }
+
+    #[test]
+    fn every_reason_has_its_own_ttl() {
+        let reasons = [
+            (BlacklistReason::Unreachable, 50),
+            (BlacklistReason::EmptyTrash, 200),
+            (BlacklistReason::FullBin, 100),
+            (BlacklistReason::RefusedSale, 100),
+            (BlacklistReason::StillBurning, 100),
+        ];
+        for (i, (reason, ttl)) in reasons.into_iter().enumerate() {
+            let mut blacklist = TargetBlacklist::new();
+            let coords = GridPos::new(i, i);
+            blacklist.ban(coords, reason, 0);
+            assert_eq!(blacklist.get_entry(coords, 0).unwrap().reason, reason);
+            assert!(blacklist.is_banned(coords, ttl - 1));
+            assert!(!blacklist.is_banned(coords, ttl));
+        }
+    }
+
+    #[test]
+    fn retain_allowed_keeps_the_order() {
+        let mut blacklist = TargetBlacklist::new();
+        blacklist.ban(PILE, BlacklistReason::Unreachable, 0);
+        let mut coords = vec![GridPos::new(0, 1), PILE, GridPos::new(1, 0)];
+
+        blacklist.retain_allowed(&mut coords, 1);
+        assert_eq!(coords, vec![GridPos::new(0, 1), GridPos::new(1, 0)]);
+        blacklist.unban(PILE);
+        assert!(!blacklist.is_banned(PILE, 1));
+    }
+}
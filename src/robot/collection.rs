@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::mem::discriminant;
+
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{grid::GridPos, map_snapshot::tile_at, Scrapbot};
+
+/// True if both contents are the same variant, whatever their quantity.
+pub fn same_kind(a: &Content, b: &Content) -> bool {
+    discriminant(a) == discriminant(b)
+}
+
+/// Quantity stored in a content, 0 for contents without one (or ranges).
+pub fn content_quantity(content: &Content) -> usize {
+    match content {
+        Content::Rock(q)
+        | Content::Tree(q)
+        | Content::Garbage(q)
+        | Content::Coin(q)
+        | Content::Water(q)
+        | Content::Fish(q)
+        | Content::Market(q) => *q,
+        _ => 0,
+    }
+}
+
+/// False for bins and banks that can't take anything more.
+pub fn has_room(content: &Content) -> bool {
+    match content {
+        Content::Bin(range) | Content::Bank(range) => range.end > range.start,
+        _ => true,
+    }
+}
+
+/// Same kind of content as `content` holding `quantity` items.
+pub fn with_quantity(content: &Content, quantity: usize) -> Content {
+    match content {
+        Content::Rock(_) => Content::Rock(quantity),
+        Content::Tree(_) => Content::Tree(quantity),
+        Content::Garbage(_) => Content::Garbage(quantity),
+        Content::Coin(_) => Content::Coin(quantity),
+        Content::Water(_) => Content::Water(quantity),
+        Content::Fish(_) => Content::Fish(quantity),
+        Content::Market(_) => Content::Market(quantity),
+        other => other.clone(),
+    }
+}
+
+impl Scrapbot {
+    /// Every known tile holding a content of the same kind as `content`,
+    /// together with the quantity lying on it.
+    pub fn scan_known_content(&self, world: &World, content: &Content) -> Vec<(GridPos, usize)> {
+        let mut found = vec![];
+        for (row, tiles) in self.known_map(world).iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                if let Some(tile) = tile {
+                    if same_kind(&tile.content, content) {
+                        found.push((GridPos::new(row, col), content_quantity(&tile.content)));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// How many items of the same kind as `content` are in the backpack.
+    /// Never panics, absent contents count as zero.
+    pub fn carried_quantity(&self, content: &Content) -> usize {
+        self.robot
+            .backpack
+            .get_contents()
+            .iter()
+            .filter(|(c, _)| same_kind(c, content))
+            .map(|(_, q)| *q)
+            .sum()
+    }
+
+    /// The backpack entry holding `content`, to hand to `put`. Falls back to
+    /// `content` itself if we don't carry any.
+    pub fn carried_content(&self, content: &Content) -> Content {
+        self.robot
+            .backpack
+            .get_contents()
+            .keys()
+            .find(|c| same_kind(c, content))
+            .cloned()
+            .unwrap_or_else(|| content.clone())
+    }
+
+    /// What's left on each pile we know about, 0 means the pile is gone.
+    pub fn piles(&self) -> &HashMap<GridPos, usize> {
+        &self.piles
+    }
+
+    /// Re-reads every known pile from the robot map, so that partially
+    /// collected piles report what's left on them, and adds the known piles
+    /// of `content`.
+    pub(crate) fn refresh_piles(&mut self, world: &World, content: &Content) {
+        let known_map = self.known_map(world);
+
+        for (coords, remaining) in self.piles.iter_mut() {
+            if let Some(tile) = tile_at(&known_map, *coords) {
+                *remaining = content_quantity(&tile.content);
+            }
+        }
+
+        for (coords, q) in self.scan_known_content(world, content) {
+            self.piles.insert(coords, q);
+        }
+    }
+
+    /// Builds collection requirements for the pile sizes of `content` we've
+    /// seen, asking for at most `free_space` items in total so the backpack
+    /// never overflows. Small piles come first, more of them fit.
+    /// Piles we haven't seen yet get what's left.
+    pub(crate) fn collection_requirements(
+        &self,
+        world: &World,
+        content: &Content,
+        free_space: usize,
+    ) -> HashMap<Content, usize> {
+        let mut sizes: Vec<usize> = self
+            .scan_known_content(world, content)
+            .into_iter()
+            .map(|(_, q)| q)
+            .filter(|q| *q > 0)
+            .collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+
+        let mut requirements = HashMap::new();
+        let mut budget = free_space;
+        for q in sizes {
+            if budget == 0 {
+                break;
+            }
+            requirements.insert(with_quantity(content, q), q.min(budget));
+            budget -= q.min(budget);
+        }
+        // piles we haven't seen yet are matched by the default variant
+        if budget > 0 {
+            requirements.insert(with_quantity(content, 0), budget);
+        }
+        requirements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::interface::{destroy, Direction};
+
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    // piles of 2, 5, 5 and 7 garbage around the middle of a 9x9 map
+    fn littered() -> TestWorld {
+        TestWorld::grass(9, 9)
+            .spawn_at(GridPos::new(4, 4))
+            .with_content(GridPos::new(4, 5), Content::Garbage(2))
+            .with_content(GridPos::new(2, 2), Content::Garbage(5))
+            .with_content(GridPos::new(6, 6), Content::Garbage(5))
+            .with_content(GridPos::new(2, 6), Content::Garbage(7))
+    }
+
+    #[test]
+    fn requirements_fill_the_budget_from_the_smallest_pile() {
+        littered().run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            let garbage = Content::Garbage(0);
+
+            let partial = bot.collection_requirements(world, &garbage, 8);
+            assert_eq!(
+                partial,
+                HashMap::from([
+                    (Content::Garbage(2), 2),
+                    (Content::Garbage(5), 5),
+                    (Content::Garbage(7), 1)
+                ])
+            );
+
+            // room left over goes to piles we haven't seen
+            let roomy = bot.collection_requirements(world, &garbage, 20);
+            assert_eq!(roomy.get(&Content::Garbage(7)), Some(&7));
+            assert_eq!(roomy.get(&Content::Garbage(0)), Some(&6));
+            assert_eq!(roomy.values().sum::<usize>(), 20);
+
+            assert!(bot.collection_requirements(world, &garbage, 0).is_empty());
+        });
+    }
+
+    #[test]
+    fn refresh_reports_what_is_left_on_known_piles() {
+        littered().run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            bot.refresh_piles(world, &Content::Garbage(0));
+            assert_eq!(bot.piles().get(&GridPos::new(4, 5)), Some(&2));
+            assert_eq!(bot.piles().len(), 4);
+
+            destroy(bot, world, Direction::Right).unwrap();
+            bot.refresh_piles(world, &Content::Garbage(0));
+            assert_eq!(bot.piles().get(&GridPos::new(4, 5)), Some(&0));
+            assert_eq!(bot.piles().get(&GridPos::new(2, 6)), Some(&7));
+        });
+    }
+
+    #[test]
+    fn only_full_bins_and_banks_have_no_room() {
+        assert!(!has_room(&Content::Bin(3..3)));
+        assert!(has_room(&Content::Bin(0..3)));
+        assert!(!has_room(&Content::Bank(10..10)));
+        assert!(has_room(&Content::Garbage(0)));
+    }
+}
@@ -0,0 +1,521 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::mem::{discriminant, Discriminant};
+
+use robotics_lib::interface::Direction;
+use robotics_lib::world::environmental_conditions::{
+    DayTime, EnvironmentalConditions, WeatherType,
+};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+use robotics_lib::world::World;
+use sense_and_find_by_rustafariani::Action;
+
+use crate::robot::{
+    grid::{action_to_direction, GridPos, DIRECTIONS},
+    map_snapshot::{map_dims, tile_at, KnownMap},
+    navigation::{is_hazard, neighbour},
+    obstacles::{is_obstacle, ClearingCost},
+    Scrapbot,
+};
+
+// tile we came from and the direction taken, for every tile
+pub(crate) type Parents = Vec<Vec<Option<(GridPos, Direction)>>>;
+
+/// Energy spent by `go` to climb from `from` to `to`: the square of the
+/// elevation difference, going down or staying level is free.
+pub fn elevation_cost(from: usize, to: usize) -> usize {
+    to.saturating_sub(from).pow(2)
+}
+
+/// Energy spent by `go` to step from `from` onto `to` with clear weather:
+/// the base cost of the destination tile type plus the climb.
+pub fn step_cost(from: &Tile, to: &Tile) -> usize {
+    to.tile_type.properties().cost() + elevation_cost(from.elevation, to.elevation)
+}
+
+type Conditions = (Discriminant<WeatherType>, Discriminant<DayTime>);
+
+fn conditions_key(conditions: &EnvironmentalConditions) -> Conditions {
+    (
+        discriminant(&conditions.get_weather_condition()),
+        discriminant(&conditions.get_time_of_day()),
+    )
+}
+
+/// What `go` charged for entering each kind of tile, per weather and time of
+/// day.
+///
+/// The library changes the base cost of the destination tile with the
+/// environmental conditions, the climb is paid on top of it, but it doesn't
+/// tell by how much. So every step we take records what it cost minus the
+/// climb, and that's what the next steps in the same conditions are
+/// expected to cost. This has nothing to do with the scheduling factors,
+/// which are preferences and not prices.
+#[derive(Debug, Clone, Default)]
+pub struct GoCosts {
+    charged: HashMap<(Conditions, Discriminant<TileType>), usize>,
+}
+
+impl GoCosts {
+    /// Records that entering a `tile_type` tile cost `base_cost` in
+    /// `conditions`, the climb left out.
+    pub fn observe(
+        &mut self,
+        conditions: &EnvironmentalConditions,
+        tile_type: &TileType,
+        base_cost: usize,
+    ) {
+        self.charged.insert(
+            (conditions_key(conditions), discriminant(tile_type)),
+            base_cost,
+        );
+    }
+
+    /// Base costs seen in `conditions`, per kind of tile.
+    pub fn base_costs(
+        &self,
+        conditions: &EnvironmentalConditions,
+    ) -> HashMap<Discriminant<TileType>, usize> {
+        let key = conditions_key(conditions);
+        self.charged
+            .iter()
+            .filter(|((conditions, _), _)| *conditions == key)
+            .map(|((_, tile_type), cost)| (*tile_type, *cost))
+            .collect()
+    }
+}
+
+/// Estimates the energy needed to move on the known map.
+///
+/// Step costs follow the library rules ([`step_cost`]): the base cost of the
+/// destination is the one `go` charged last time in the current conditions
+/// (`base_costs`, see [`GoCosts`]) or the tile property if we haven't walked
+/// on that kind of tile yet, the climb is added on top. Undiscovered tiles
+/// count as `unknown_tile_cost` and hazards can't be crossed. Trees and rocks
+/// can be crossed only with a `clearing` cost.
+#[derive(Debug, Clone)]
+pub struct CostModel {
+    pub base_costs: HashMap<Discriminant<TileType>, usize>,
+    pub unknown_tile_cost: usize,
+    pub clearing: Option<ClearingCost>,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            base_costs: HashMap::new(),
+            unknown_tile_cost: 3,
+            clearing: None,
+        }
+    }
+}
+
+impl CostModel {
+    /// Energy `go` charges to enter a `tile_type` tile, without the climb.
+    pub fn base_cost(&self, tile_type: &TileType) -> usize {
+        self.base_costs
+            .get(&discriminant(tile_type))
+            .copied()
+            .unwrap_or_else(|| tile_type.properties().cost())
+    }
+
+    /// Cost of one step between two adjacent tiles, `None` if `to` can't be
+    /// walked on.
+    pub fn step(&self, known_map: &KnownMap, from: GridPos, to: GridPos) -> Option<usize> {
+        let (rows, cols) = map_dims(known_map);
+        if !from.is_within(rows, cols) || !to.is_within(rows, cols) {
+            return None;
+        }
+        let from_tile = tile_at(known_map, from);
+        let to_tile = tile_at(known_map, to);
+        let (base, climb) = match (from_tile, to_tile) {
+            (_, Some(tile)) if is_hazard(&tile.tile_type) => return None,
+            (Some(from_tile), Some(tile)) => (
+                self.base_cost(&tile.tile_type),
+                elevation_cost(from_tile.elevation, tile.elevation),
+            ),
+            (None, Some(tile)) => (self.base_cost(&tile.tile_type), 0),
+            (_, None) => (self.unknown_tile_cost, 0),
+        };
+        let clearing = match to_tile {
+            Some(tile) if is_obstacle(&tile.content) => self.clearing?.for_tile(tile)?,
+            _ => 0,
+        };
+        Some(base + climb + clearing)
+    }
+
+    /// Cheapest cost from `from` to every tile of the known map (Dijkstra),
+    /// `usize::MAX` for the ones we can't reach.
+    ///
+    /// With `walk_unknown` undiscovered tiles can be crossed, otherwise only
+    /// known tiles are used.
+    pub fn costs_from(
+        &self,
+        known_map: &KnownMap,
+        from: GridPos,
+        walk_unknown: bool,
+    ) -> Vec<Vec<usize>> {
+        self.dijkstra(known_map, from, walk_unknown).0
+    }
+
+    /// Cheapest known route between two tiles, undiscovered tiles included.
+    /// The destination may be a tile we can't stand on (a pile on a hill), in
+    /// that case we stop next to it.
+    pub fn path_cost(&self, known_map: &KnownMap, from: GridPos, to: GridPos) -> Option<usize> {
+        let costs = self.costs_from(known_map, from, true);
+        reach_cost(&costs, known_map, to)
+    }
+
+    /// Cheapest cost from every tile to the nearest of `targets`, in one
+    /// Dijkstra run backwards from all of them, `usize::MAX` where none can be
+    /// reached. Matches [`reach_cost`] on a [`CostModel::costs_from`] grid:
+    /// targets we can't stand on are reached from next to them.
+    pub fn costs_to(
+        &self,
+        known_map: &KnownMap,
+        targets: &[GridPos],
+        walk_unknown: bool,
+    ) -> Vec<Vec<usize>> {
+        let (rows, cols) = map_dims(known_map);
+        let mut costs = vec![vec![usize::MAX; cols]; rows];
+        let mut heap = BinaryHeap::new();
+        let enterable = |to: GridPos| {
+            (walk_unknown || tile_at(known_map, to).is_some())
+                && to
+                    .neighbours(rows, cols)
+                    .any(|(_, from)| self.step(known_map, from, to).is_some())
+        };
+        for target in targets.iter().filter(|t| t.is_within(rows, cols)) {
+            let mut seeds = vec![*target];
+            if !enterable(*target) {
+                seeds.extend(target.neighbours(rows, cols).map(|(_, tile)| tile));
+            }
+            for seed in seeds {
+                costs[seed.row][seed.col] = 0;
+                heap.push(Reverse((0usize, seed)));
+            }
+        }
+
+        while let Some(Reverse((cost, current))) = heap.pop() {
+            if cost > costs[current.row][current.col] {
+                continue;
+            }
+            // every neighbour steps onto `current` on its way
+            if !walk_unknown && tile_at(known_map, current).is_none() {
+                continue;
+            }
+            for (_, previous) in current.neighbours(rows, cols) {
+                let Some(step) = self.step(known_map, previous, current) else {
+                    continue;
+                };
+                if cost + step < costs[previous.row][previous.col] {
+                    costs[previous.row][previous.col] = cost + step;
+                    heap.push(Reverse((cost + step, previous)));
+                }
+            }
+        }
+        costs
+    }
+
+    /// Costs and parents (previous tile, direction taken) of every tile.
+    pub(crate) fn dijkstra(
+        &self,
+        known_map: &KnownMap,
+        from: GridPos,
+        walk_unknown: bool,
+    ) -> (Vec<Vec<usize>>, Parents) {
+        let (rows, cols) = map_dims(known_map);
+        let mut costs = vec![vec![usize::MAX; cols]; rows];
+        let mut parents = vec![vec![None; cols]; rows];
+        if !from.is_within(rows, cols) {
+            return (costs, parents);
+        }
+
+        let mut heap = BinaryHeap::new();
+        costs[from.row][from.col] = 0;
+        heap.push(Reverse((0usize, from)));
+
+        while let Some(Reverse((cost, current))) = heap.pop() {
+            if cost > costs[current.row][current.col] {
+                continue;
+            }
+            for (direction, next) in current.neighbours(rows, cols) {
+                if !walk_unknown && tile_at(known_map, next).is_none() {
+                    continue;
+                }
+                let Some(step) = self.step(known_map, current, next) else {
+                    continue;
+                };
+                if cost + step < costs[next.row][next.col] {
+                    costs[next.row][next.col] = cost + step;
+                    parents[next.row][next.col] = Some((current, direction));
+                    heap.push(Reverse((cost + step, next)));
+                }
+            }
+        }
+        (costs, parents)
+    }
+}
+
+/// Cost of reaching `to` in a [`CostModel::costs_from`] grid, or of reaching
+/// the cheapest tile next to it if we can't stand on it.
+pub fn reach_cost(costs: &[Vec<usize>], known_map: &KnownMap, to: GridPos) -> Option<usize> {
+    let cost = *costs.get(to.row)?.get(to.col)?;
+    if cost != usize::MAX {
+        return Some(cost);
+    }
+    DIRECTIONS
+        .iter()
+        .filter_map(|direction| neighbour(known_map, to, direction))
+        .map(|next| costs[next.row][next.col])
+        .filter(|cost| *cost != usize::MAX)
+        .min()
+}
+
+/// Rebuilds the directions leading to `to` from the Dijkstra parents.
+pub(crate) fn walk_back(parents: &Parents, to: GridPos) -> Vec<Direction> {
+    let mut path = vec![];
+    let mut current = to;
+    while let Some((previous, direction)) = parents[current.row][current.col].clone() {
+        path.push(direction);
+        current = previous;
+    }
+    path.reverse();
+    path
+}
+
+impl Scrapbot {
+    /// Cost model for the last observed weather and time of day.
+    pub fn cost_model(&self) -> CostModel {
+        let base_costs = match &self.environment {
+            Some(conditions) => self.go_costs.base_costs(conditions),
+            None => HashMap::new(),
+        };
+        CostModel {
+            base_costs,
+            clearing: self.obstacles.clearing_cost(
+                self.takeable_quantity(&Content::Tree(0)),
+                self.takeable_quantity(&Content::Rock(0)),
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// Estimated energy to run an LSSF plan from where we are, teleports
+    /// excluded. `None` if the plan crosses a hazard.
+    pub(crate) fn plan_energy(&self, world: &World, actions: &[Action]) -> Option<usize> {
+        let known_map = self.known_map(world);
+        let model = self.cost_model();
+        let mut position = self.position();
+        let mut energy = 0;
+        for action in actions {
+            let direction = match action_to_direction(action) {
+                Some(direction) => direction,
+                None => {
+                    position = position.step_action(action)?;
+                    continue;
+                }
+            };
+            let next = neighbour(&known_map, position, &direction)?;
+            energy += model.step(&known_map, position, next)?;
+            position = next;
+        }
+        Some(energy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::interface::look_at_sky;
+
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    fn tile(tile_type: TileType, elevation: usize) -> Tile {
+        Tile {
+            tile_type,
+            content: Content::None,
+            elevation,
+        }
+    }
+
+    fn base(tile_type: TileType) -> usize {
+        tile_type.properties().cost()
+    }
+
+    // one row: whatever `left` and `right` are
+    fn row(left: Option<Tile>, right: Option<Tile>) -> KnownMap {
+        vec![vec![left, right]]
+    }
+
+    const LEFT: GridPos = GridPos::new(0, 0);
+    const RIGHT: GridPos = GridPos::new(0, 1);
+
+    #[test]
+    fn climbing_costs_the_square_of_the_difference() {
+        assert_eq!(elevation_cost(2, 5), 9);
+        assert_eq!(elevation_cost(0, 1), 1);
+        assert_eq!(elevation_cost(3, 3), 0);
+    }
+
+    #[test]
+    fn going_down_is_free() {
+        assert_eq!(elevation_cost(5, 2), 0);
+        assert_eq!(
+            step_cost(&tile(TileType::Grass, 8), &tile(TileType::Sand, 1)),
+            base(TileType::Sand)
+        );
+    }
+
+    #[test]
+    fn step_cost_adds_the_climb_to_the_destination_cost() {
+        assert_eq!(
+            step_cost(&tile(TileType::Grass, 1), &tile(TileType::Hill, 3)),
+            base(TileType::Hill) + 4
+        );
+    }
+
+    #[test]
+    fn clear_weather_step_matches_step_cost() {
+        let from = tile(TileType::Grass, 0);
+        let to = tile(TileType::Grass, 2);
+        let map = row(Some(from.clone()), Some(to.clone()));
+        assert_eq!(
+            CostModel::default().step(&map, LEFT, RIGHT),
+            Some(step_cost(&from, &to))
+        );
+    }
+
+    // what `go` would charge for grass in bad weather
+    fn charged_grass(cost: usize) -> CostModel {
+        CostModel {
+            base_costs: HashMap::from([(discriminant(&TileType::Grass), cost)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn charged_base_cost_replaces_the_tile_cost_but_not_the_climb() {
+        let map = row(
+            Some(tile(TileType::Grass, 0)),
+            Some(tile(TileType::Grass, 2)),
+        );
+        let model = charged_grass(base(TileType::Grass) + 5);
+        assert_eq!(
+            model.step(&map, LEFT, RIGHT),
+            Some(base(TileType::Grass) + 5 + 4)
+        );
+        // going back down only pays the base cost
+        assert_eq!(
+            model.step(&map, RIGHT, LEFT),
+            Some(base(TileType::Grass) + 5)
+        );
+    }
+
+    #[test]
+    fn tiles_never_walked_on_cost_their_property() {
+        let map = row(
+            Some(tile(TileType::Grass, 0)),
+            Some(tile(TileType::Sand, 0)),
+        );
+        let model = charged_grass(base(TileType::Grass) + 5);
+        assert_eq!(model.step(&map, LEFT, RIGHT), Some(base(TileType::Sand)));
+    }
+
+    #[test]
+    fn unknown_origin_skips_the_elevation() {
+        let map = row(None, Some(tile(TileType::Grass, 7)));
+        let model = charged_grass(base(TileType::Grass) * 2);
+        assert_eq!(
+            model.step(&map, LEFT, RIGHT),
+            Some(base(TileType::Grass) * 2)
+        );
+    }
+
+    #[test]
+    fn unknown_destination_uses_the_flat_cost() {
+        let map = row(Some(tile(TileType::Grass, 0)), None);
+        let model = CostModel::default();
+        assert_eq!(model.step(&map, LEFT, RIGHT), Some(model.unknown_tile_cost));
+    }
+
+    #[test]
+    fn hazards_and_the_outside_are_never_walked() {
+        let map = row(
+            Some(tile(TileType::Grass, 0)),
+            Some(tile(TileType::Lava, 0)),
+        );
+        let model = CostModel::default();
+        assert_eq!(model.step(&map, LEFT, RIGHT), None);
+        assert_eq!(model.step(&map, LEFT, GridPos::new(1, 0)), None);
+    }
+
+    #[test]
+    fn costs_to_targets_match_the_costs_from_every_tile() {
+        // a hill, a hole of lava and a bin we can't stand on
+        let mut map: KnownMap = vec![vec![Some(tile(TileType::Grass, 0)); 5]; 4];
+        map[1][1] = Some(tile(TileType::Hill, 3));
+        map[2][3] = Some(tile(TileType::Lava, 0));
+        map[3][0] = None;
+        map[0][4] = Some(tile(TileType::Lava, 0));
+        let targets = [GridPos::new(3, 4), GridPos::new(0, 4)];
+        let model = CostModel::default();
+
+        let field = model.costs_to(&map, &targets, true);
+        for row in 0..4 {
+            for col in 0..5 {
+                let from = GridPos::new(row, col);
+                let costs = model.costs_from(&map, from, true);
+                let expected = targets
+                    .iter()
+                    .filter_map(|target| reach_cost(&costs, &map, *target))
+                    .min()
+                    .unwrap_or(usize::MAX);
+                assert_eq!(field[row][col], expected, "from {}", from);
+            }
+        }
+    }
+
+    #[test]
+    fn model_matches_go_in_any_weather_day_and_night() {
+        let weathers = [
+            WeatherType::Sunny,
+            WeatherType::Rainy,
+            WeatherType::Foggy,
+            WeatherType::TropicalMonsoon,
+            WeatherType::TrentinoSnow,
+        ];
+        for weather in weathers {
+            for hour in [12, 2] {
+                // a flat step to learn the price, then another one and a
+                // climb of 2
+                let world = TestWorld::grass(9, 9)
+                    .spawn_at(GridPos::new(4, 4))
+                    .with_elevation(GridPos::new(4, 7), 2)
+                    .with_weather(weather)
+                    .at_hour(hour);
+                world.run(test_bot(), move |bot, world| {
+                    bot.lssf_update(world, Some(7));
+                    bot.environment = Some(look_at_sky(world));
+                    assert!(bot.step(world, Direction::Right));
+
+                    let model = bot.cost_model();
+                    let known_map = bot.known_map(world);
+                    let predicted = vec![
+                        model.step(&known_map, GridPos::new(4, 5), GridPos::new(4, 6)),
+                        model.step(&known_map, GridPos::new(4, 6), GridPos::new(4, 7)),
+                    ];
+                    drop(known_map);
+
+                    let mut spent = vec![];
+                    for _ in 0..2 {
+                        let before = bot.metrics.energy_consumed;
+                        assert!(bot.step(world, Direction::Right));
+                        spent.push(Some(bot.metrics.energy_consumed - before));
+                    }
+                    assert_eq!(predicted, spent, "{:?} at {}:00", weather, hour);
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use robotics_lib::runner::Runnable;
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{blacklist::BlacklistReason, collection::has_room, grid::GridPos, Scrapbot};
+
+/// Energy we always keep to be able to reach a bin with space.
+///
+/// The reserve is the estimated cost of the way to the nearest known bin with
+/// space, increased by `margin_ratio` and `margin` to absorb estimate errors
+/// (unknown tiles, weather changes).
+#[derive(Debug, Clone)]
+pub struct EnergyReserve {
+    pub enabled: bool,
+    pub margin_ratio: f32,
+    pub margin: usize,
+}
+
+impl Default for EnergyReserve {
+    fn default() -> Self {
+        EnergyReserve {
+            enabled: true,
+            margin_ratio: 0.2,
+            margin: 10,
+        }
+    }
+}
+
+impl EnergyReserve {
+    pub fn disabled() -> EnergyReserve {
+        EnergyReserve {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Reserve needed when the nearest bin costs `bin_cost` to reach.
+    pub fn required(&self, bin_cost: usize) -> usize {
+        (bin_cost as f32 * (1.0 + self.margin_ratio)).ceil() as usize + self.margin
+    }
+}
+
+/// Cost from every tile to the nearest known bin with space, computed once
+/// per tick: the reserve is checked for every target we consider.
+#[derive(Debug, Default)]
+pub struct BinCosts {
+    tick: usize,
+    costs: Option<Arc<Vec<Vec<usize>>>>,
+}
+
+impl Scrapbot {
+    /// Estimated energy to reach the nearest known bin that still has space
+    /// and isn't banned, `None` if we don't know any.
+    pub fn energy_to_nearest_bin(&self, world: &World) -> Option<usize> {
+        self.energy_to_nearest_bin_from(world, self.position())
+    }
+
+    /// Same as [`Scrapbot::energy_to_nearest_bin`], starting from `from`.
+    pub fn energy_to_nearest_bin_from(&self, world: &World, from: GridPos) -> Option<usize> {
+        self.bin_cost_field(world)
+            .get(from.row)?
+            .get(from.col)
+            .copied()
+            .filter(|cost| *cost != usize::MAX)
+    }
+
+    // bins found later in the tick wait for the next one
+    fn bin_cost_field(&self, world: &World) -> Arc<Vec<Vec<usize>>> {
+        let mut cache = self.bin_costs.borrow_mut();
+        if cache.tick == self.tick {
+            if let Some(costs) = &cache.costs {
+                return Arc::clone(costs);
+            }
+        }
+
+        let known_map = self.known_map(world);
+        let mut bins = vec![];
+        for (row, tiles) in known_map.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                if let Some(tile) = tile {
+                    if matches!(tile.content, Content::Bin(_)) && has_room(&tile.content) {
+                        bins.push(GridPos::new(row, col));
+                    }
+                }
+            }
+        }
+        self.blacklist.retain_allowed(&mut bins, self.tick);
+
+        let costs = Arc::new(self.cost_model().costs_to(&known_map, &bins, true));
+        cache.tick = self.tick;
+        cache.costs = Some(Arc::clone(&costs));
+        costs
+    }
+
+    /// Energy we must not spend, 0 if the policy is off or no bin is known.
+    pub fn energy_reserve(&self, world: &World) -> usize {
+        self.energy_reserve_from(world, self.position())
+    }
+
+    /// Reserve we would need standing on `from`.
+    pub fn energy_reserve_from(&self, world: &World, from: GridPos) -> usize {
+        if !self.reserve.enabled {
+            return 0;
+        }
+        self.energy_to_nearest_bin_from(world, from)
+            .map_or(0, |cost| self.reserve.required(cost))
+    }
+
+    /// True if we're already using the energy kept for the way to a bin.
+    pub fn reserve_breached(&self, world: &World) -> bool {
+        self.get_energy().get_energy_level() < self.energy_reserve(world)
+    }
+
+    /// Checks the planned path to `target` against the reserve, the plan is
+    /// dropped if running it would leave us without enough energy to reach a
+    /// bin from where it ends. We rest until we could afford it, unless not
+    /// even a full battery would do: then `target` is banned as unreachable.
+    pub(crate) fn can_afford_plan(&mut self, world: &World, target: GridPos) -> bool {
+        let plan = self.actions_vec.clone().unwrap_or_default();
+        let destination = self.actions_destination(&plan);
+        let needed = self.plan_energy(world, &plan).unwrap_or(0)
+            + self.energy_reserve_from(world, destination);
+        let level = self.get_energy().get_energy_level();
+        if level >= needed {
+            return true;
+        }
+        if needed > self.recharge.capacity {
+            self.blacklist
+                .ban(target, BlacklistReason::Unreachable, self.tick);
+        } else {
+            // come back to it once we can afford it
+            self.recharge.rest_until(level, needed);
+        }
+        self.actions_vec = Some(vec![]);
+        self.metrics.tours_aborted += 1;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::interface::Direction;
+
+    use super::*;
+    use crate::robot::{
+        grid::direction_to_action,
+        test_world::{test_bot, TestWorld},
+    };
+
+    // 3x6 grass, the bin and our spawn sit on a plateau, the pile down below:
+    // getting there is free, climbing back costs more than a full battery
+    fn bin_behind_a_cliff() -> TestWorld {
+        let mut world = TestWorld::grass(3, 6)
+            .spawn_at(GridPos::new(1, 1))
+            .with_content(GridPos::new(1, 0), Content::Bin(0..10))
+            .with_content(GridPos::new(1, 5), Content::Garbage(1));
+        for row in 0..3 {
+            for col in 0..2 {
+                world = world.with_elevation(GridPos::new(row, col), 40);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn reserve_is_checked_where_the_plan_ends() {
+        bin_behind_a_cliff().run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(5));
+            let plan: Vec<_> = (0..3)
+                .map(|_| direction_to_action(&Direction::Right))
+                .collect();
+            let destination = bot.actions_destination(&plan);
+            assert_eq!(destination, GridPos::new(1, 4));
+
+            // from here the bin is next door, from the end of the plan it
+            // isn't
+            let energy = bot.get_energy().get_energy_level();
+            assert!(bot.energy_reserve(world) < energy);
+            assert!(bot.energy_reserve_from(world, destination) > energy);
+
+            let aborted = bot.metrics.tours_aborted;
+            bot.actions_vec = Some(plan);
+            let pile = GridPos::new(1, 5);
+            assert!(!bot.can_afford_plan(world, pile));
+            assert!(bot.actions_vec.as_ref().is_some_and(|plan| plan.is_empty()));
+            assert_eq!(bot.metrics.tours_aborted, aborted + 1);
+
+            // no amount of rest pays for the climb back
+            assert!(bot.blacklist.is_banned(pile, bot.tick));
+            assert!(!bot.recharge.is_resting());
+        });
+    }
+}
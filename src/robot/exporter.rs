@@ -0,0 +1,363 @@
+//! Prometheus text exposition of [`Metrics`], enabled by the `prometheus`
+//! feature.
+//!
+//! Every `every` ticks the metrics are rendered and either written to a file
+//! (for the node exporter textfile collector) or served on a local TCP socket.
+//! The TCP server stops when the exporter is dropped or shut down.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::robot::{metrics::Metrics, Scrapbot};
+
+enum ExportTarget {
+    File(PathBuf),
+    Tcp(Arc<Mutex<String>>, TcpServer),
+}
+
+// the thread serving the metrics, stopped by raising the flag and waking
+// the listener with a connection of our own
+struct TcpServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TcpServer {
+    fn shutdown(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stop.store(true, Ordering::SeqCst);
+            TcpStream::connect(self.addr).ok();
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for TcpServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Escapes a label value as the text format wants it.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub struct PrometheusExporter {
+    target: ExportTarget,
+    every: usize,
+    bot: String,
+}
+
+impl PrometheusExporter {
+    /// Writes the metrics to `path` every `every` ticks.
+    pub fn to_file(path: impl Into<PathBuf>, every: usize) -> PrometheusExporter {
+        PrometheusExporter {
+            target: ExportTarget::File(path.into()),
+            every: every.max(1),
+            bot: "scrapbot".to_string(),
+        }
+    }
+
+    /// Serves the metrics over HTTP on `addr`, refreshed every `every` ticks.
+    pub fn bind(addr: impl ToSocketAddrs, every: usize) -> io::Result<PrometheusExporter> {
+        let listener = TcpListener::bind(addr)?;
+        let mut local = listener.local_addr()?;
+        if local.ip().is_unspecified() {
+            local.set_ip(Ipv4Addr::LOCALHOST.into());
+        }
+        let body = Arc::new(Mutex::new(String::new()));
+        let served = Arc::clone(&body);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+
+        let thread = thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                // we don't care about the request, every path gets the metrics
+                BufReader::new(&stream).read_line(&mut String::new()).ok();
+
+                let body = served.lock().unwrap().clone();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).ok();
+            }
+        });
+
+        Ok(PrometheusExporter {
+            target: ExportTarget::Tcp(
+                body,
+                TcpServer {
+                    addr: local,
+                    stop,
+                    thread: Some(thread),
+                },
+            ),
+            every: every.max(1),
+            bot: "scrapbot".to_string(),
+        })
+    }
+
+    /// Address the metrics are served on, `None` when writing to a file.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.target {
+            ExportTarget::File(_) => None,
+            ExportTarget::Tcp(_, server) => Some(server.addr),
+        }
+    }
+
+    /// Stops serving the metrics and waits for the server thread, does
+    /// nothing when writing to a file.
+    pub fn shutdown(&mut self) {
+        if let ExportTarget::Tcp(_, server) = &mut self.target {
+            server.shutdown();
+        }
+    }
+
+    /// Label used to tell bots apart when several export to the same place.
+    pub fn with_bot_label(mut self, bot: impl Into<String>) -> PrometheusExporter {
+        self.bot = bot.into();
+        self
+    }
+
+    pub fn render(&self, metrics: &Metrics) -> String {
+        let mut out = String::new();
+        let bot = escape_label(&self.bot);
+        let counters = [
+            ("ticks", "Ticks run", metrics.ticks),
+            (
+                "garbage_collected",
+                "Garbage picked up",
+                metrics.garbage_collected,
+            ),
+            (
+                "garbage_deposited",
+                "Garbage put into bins",
+                metrics.garbage_deposited,
+            ),
+            ("distance_walked", "Tiles walked", metrics.distance_walked),
+            ("teleports", "Teleports used", metrics.teleports),
+            ("lssf_scans", "LSSF scans", metrics.lssf_scans),
+            (
+                "lssf_scans_skipped",
+                "LSSF scans skipped by the cache",
+                metrics.lssf_scans_skipped,
+            ),
+            (
+                "lssf_tiles_discovered",
+                "Tiles first seen by LSSF scans",
+                metrics.lssf_tiles_discovered,
+            ),
+            (
+                "lssf_scan_time_microseconds",
+                "Time spent in LSSF scans",
+                metrics.lssf_scan_time.as_micros() as usize,
+            ),
+            ("spyglass_scans", "Spyglass scans", metrics.spyglass_scans),
+            (
+                "teleport_shortcuts",
+                "Walking plans replaced by a teleport trip",
+                metrics.teleport_shortcuts,
+            ),
+            (
+                "hazards_avoided",
+                "Steps refused because of hazards",
+                metrics.hazards_avoided,
+            ),
+            (
+                "obstacles_cleared",
+                "Trees and rocks destroyed to clear the way",
+                metrics.obstacles_cleared,
+            ),
+            (
+                "fires_extinguished",
+                "Fires put out with water",
+                metrics.fires_extinguished,
+            ),
+            ("items_sold", "Items sold at markets", metrics.items_sold),
+            (
+                "coins_earned",
+                "Coins earned at markets",
+                metrics.coins_earned,
+            ),
+            (
+                "tours_aborted",
+                "Collection tours cut short by the energy reserve",
+                metrics.tours_aborted,
+            ),
+            (
+                "ticks_resting",
+                "Ticks spent idle waiting for energy",
+                metrics.ticks_resting,
+            ),
+            (
+                "failed_moves",
+                "Moves refused by the world",
+                metrics.failed_moves,
+            ),
+            (
+                "energy_consumed",
+                "Energy consumed",
+                metrics.energy_consumed,
+            ),
+        ];
+
+        for (name, help, value) in counters {
+            writeln!(out, "# HELP scrapbot_{}_total {}", name, help).ok();
+            writeln!(out, "# TYPE scrapbot_{}_total counter", name).ok();
+            writeln!(out, "scrapbot_{}_total{{bot=\"{}\"}} {}", name, bot, value).ok();
+        }
+
+        writeln!(
+            out,
+            "# HELP scrapbot_tiles_discovered Tiles in the known map"
+        )
+        .ok();
+        writeln!(out, "# TYPE scrapbot_tiles_discovered gauge").ok();
+        writeln!(
+            out,
+            "scrapbot_tiles_discovered{{bot=\"{}\"}} {}",
+            bot, metrics.tiles_discovered
+        )
+        .ok();
+
+        writeln!(
+            out,
+            "# HELP scrapbot_routine_outcomes_total Routine results by outcome"
+        )
+        .ok();
+        writeln!(out, "# TYPE scrapbot_routine_outcomes_total counter").ok();
+        for ((routine, outcome), count) in &metrics.routine_outcomes {
+            writeln!(
+                out,
+                "scrapbot_routine_outcomes_total{{bot=\"{}\",routine=\"{}\",outcome=\"{}\"}} {}",
+                bot,
+                escape_label(routine),
+                escape_label(outcome),
+                count
+            )
+            .ok();
+        }
+
+        out
+    }
+
+    /// Exports the metrics if `tick` is one of ours.
+    pub(crate) fn on_tick(&self, tick: usize, metrics: &Metrics) -> io::Result<()> {
+        if tick % self.every != 0 {
+            return Ok(());
+        }
+
+        let body = self.render(metrics);
+        match &self.target {
+            ExportTarget::File(path) => {
+                // write aside and rename so scrapers never read half a file
+                let tmp = path.with_extension("tmp");
+                fs::write(&tmp, body)?;
+                fs::rename(tmp, path)
+            }
+            ExportTarget::Tcp(served, _) => {
+                *served.lock().unwrap() = body;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Scrapbot {
+    pub fn with_exporter(mut self, exporter: PrometheusExporter) -> Scrapbot {
+        self.exporter = Some(exporter);
+        self
+    }
+
+    pub(crate) fn export_metrics(&self) {
+        if let Some(exporter) = &self.exporter {
+            if let Err(err) = exporter.on_tick(self.tick, &self.metrics) {
+                log::warn!("Error exporting metrics: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_labels_every_sample_with_the_bot() {
+        let exporter = PrometheusExporter::to_file("unused.prom", 10).with_bot_label("bot-1");
+        let mut metrics = Metrics::new();
+        metrics.ticks = 42;
+        metrics.garbage_collected = 7;
+        metrics.tiles_discovered = 120;
+        metrics.record_routine("collect_resource", "success");
+        metrics.record_routine("collect_resource", "success");
+        metrics.record_routine("deliver_resource", "found_full_bin");
+
+        let out = exporter.render(&metrics);
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert!(lines.contains(&"# TYPE scrapbot_ticks_total counter"));
+        assert!(lines.contains(&"scrapbot_ticks_total{bot=\"bot-1\"} 42"));
+        assert!(lines.contains(&"scrapbot_garbage_collected_total{bot=\"bot-1\"} 7"));
+        assert!(lines.contains(&"# TYPE scrapbot_tiles_discovered gauge"));
+        assert!(lines.contains(&"scrapbot_tiles_discovered{bot=\"bot-1\"} 120"));
+        assert!(lines.contains(
+            &"scrapbot_routine_outcomes_total{bot=\"bot-1\",routine=\"collect_resource\",outcome=\"success\"} 2"
+        ));
+        assert!(lines.contains(
+            &"scrapbot_routine_outcomes_total{bot=\"bot-1\",routine=\"deliver_resource\",outcome=\"found_full_bin\"} 1"
+        ));
+        // one sample per metric, the outcomes family has one per outcome
+        let samples = lines.iter().filter(|line| !line.starts_with('#')).count();
+        let families = lines
+            .iter()
+            .filter(|line| line.starts_with("# TYPE"))
+            .count();
+        assert_eq!(samples, families - 1 + metrics.routine_outcomes.len());
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let exporter = PrometheusExporter::to_file("unused.prom", 10).with_bot_label("a\"b\\c\nd");
+        let out = exporter.render(&Metrics::new());
+        assert!(out
+            .lines()
+            .any(|line| line == "scrapbot_ticks_total{bot=\"a\\\"b\\\\c\\nd\"} 0"));
+        assert_eq!(escape_label("plain"), "plain");
+    }
+
+    #[test]
+    fn tcp_server_serves_until_shut_down() {
+        let mut exporter = PrometheusExporter::bind("127.0.0.1:0", 1).unwrap();
+        let addr = exporter.local_addr().unwrap();
+        let mut metrics = Metrics::new();
+        metrics.ticks = 3;
+        exporter.on_tick(1, &metrics).unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("scrapbot_ticks_total{bot=\"scrapbot\"} 3"));
+
+        exporter.shutdown();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use robotics_lib::utils::LibError;
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{
+    blacklist::BlacklistReason,
+    grid::GridPos,
+    navigation::{neighbour, plan_safe_path},
+    resources::ResourceRule,
+    routines::RoutineResult,
+    Scrapbot,
+};
+
+/// Optional firefighting: fires close to the way to the nearest bin are put
+/// out with water.
+///
+/// `priority` is compared with the [`ResourceRule`] priorities to decide when
+/// the routine looks at fires, garbage has priority 10.
+#[derive(Debug, Clone)]
+pub struct FirefightingPolicy {
+    pub enabled: bool,
+    pub priority: u8,
+    /// A fire this close (in tiles) to the path to a bin is a threat.
+    pub threat_radius: usize,
+    /// Water used to put out one fire.
+    pub water_per_fire: usize,
+    fires: Vec<GridPos>,
+}
+
+impl Default for FirefightingPolicy {
+    fn default() -> Self {
+        FirefightingPolicy {
+            enabled: false,
+            priority: 12,
+            threat_radius: 2,
+            water_per_fire: 1,
+            fires: vec![],
+        }
+    }
+}
+
+impl FirefightingPolicy {
+    pub fn new(priority: u8) -> FirefightingPolicy {
+        FirefightingPolicy {
+            enabled: true,
+            priority,
+            ..Default::default()
+        }
+    }
+
+    /// Fires seen in the known map at the last refresh.
+    pub fn get_fires(&self) -> &[GridPos] {
+        &self.fires
+    }
+}
+
+impl Scrapbot {
+    /// Turns firefighting on, fires are looked at before every resource with
+    /// a lower priority.
+    pub fn with_firefighting(mut self, priority: u8) -> Scrapbot {
+        self.firefighting = FirefightingPolicy::new(priority);
+        self
+    }
+
+    /// Whether fires are looked at before collecting `rule`, ties go to the
+    /// fires.
+    pub(crate) fn fires_come_before(&self, rule: &ResourceRule) -> bool {
+        self.firefighting.enabled && self.firefighting.priority >= rule.priority
+    }
+
+    pub(crate) fn refresh_fires(&mut self, world: &World) {
+        self.firefighting.fires = self
+            .scan_known_content(world, &Content::Fire)
+            .into_iter()
+            .map(|(coords, _)| coords)
+            .collect();
+    }
+
+    /// Known fires close to the path to the nearest bin, nearest first.
+    /// Banned fires and bins are left out.
+    pub(crate) fn threatening_fires(&self, world: &World) -> Vec<GridPos> {
+        if self.firefighting.fires.is_empty() {
+            return vec![];
+        }
+
+        let mut bins: Vec<GridPos> = self
+            .scan_known_content(world, &Content::Bin(0..0))
+            .into_iter()
+            .map(|(coords, _)| coords)
+            .collect();
+        self.blacklist.retain_allowed(&mut bins, self.tick);
+        self.sort_from_nearest(world, &mut bins);
+        let bin = match bins.first() {
+            Some(bin) => *bin,
+            None => return vec![],
+        };
+
+        let known_map = self.known_map(world);
+        let path = plan_safe_path(&known_map, &self.cost_model(), self.position(), bin, false)
+            .unwrap_or_default();
+        let mut corridor = vec![self.position()];
+        for direction in &path {
+            match neighbour(&known_map, *corridor.last().unwrap(), direction) {
+                Some(next) => corridor.push(next),
+                None => break,
+            }
+        }
+        corridor.push(bin);
+
+        let radius = self.firefighting.threat_radius;
+        let mut threats: Vec<GridPos> = self
+            .firefighting
+            .fires
+            .iter()
+            .filter(|fire| corridor.iter().any(|tile| tile.manhattan(**fire) <= radius))
+            .copied()
+            .collect();
+        self.blacklist.retain_allowed(&mut threats, self.tick);
+        self.sort_from_nearest(world, &mut threats);
+        threats
+    }
+
+    /// Puts out the nearest threatening fire, fetching water first if we
+    /// don't carry enough.
+    pub(crate) fn routine_fight_fire(
+        &mut self,
+        world: &mut World,
+    ) -> Result<RoutineResult, LibError> {
+        if !self.firefighting.enabled {
+            return Ok(RoutineResult::NoChanges);
+        }
+
+        self.refresh_fires(world);
+        let threats = self.threatening_fires(world);
+        let fire = match threats.first() {
+            Some(fire) => *fire,
+            None => return Ok(RoutineResult::NoChanges),
+        };
+
+        let water_per_fire = self.firefighting.water_per_fire;
+        if self.carried_quantity(&Content::Water(0)) < water_per_fire {
+            // only take the water we need for the fires we know about
+            let water = Content::Water(0);
+            self.backpack_manager
+                .set_quota(&water, water_per_fire * threats.len());
+            let rule = ResourceRule::new(water.clone(), self.firefighting.priority, vec![]);
+            let result = self.routine_collect_resource(world, &rule);
+            self.backpack_manager.clear_quota(&water);
+            return result;
+        }
+
+        // fires we can't get to or can't put out are left alone for a while,
+        // they'd be the nearest threat again on the next tick
+        let direction = match self.approach_adjacent(world, fire, false) {
+            Ok(direction) => direction,
+            Err(LibError::CannotWalk) => {
+                self.blacklist
+                    .ban(fire, BlacklistReason::Unreachable, self.tick);
+                return Ok(RoutineResult::NoChanges);
+            }
+            Err(err) => return Err(err),
+        };
+        match self.drop_content_in_front_of(world, &Content::Water(0), water_per_fire, direction) {
+            Ok(placed) if placed > 0 => {}
+            Ok(_) | Err(LibError::OperationNotAllowed) | Err(LibError::WrongContentUsed) => {
+                self.blacklist
+                    .ban(fire, BlacklistReason::StillBurning, self.tick);
+                return Ok(RoutineResult::NoChanges);
+            }
+            Err(err) => return Err(err),
+        }
+        self.metrics.fires_extinguished += 1;
+        self.firefighting.fires.retain(|coords| *coords != fire);
+        Ok(RoutineResult::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    const SPAWN: GridPos = GridPos { row: 4, col: 4 };
+    const BIN: GridPos = GridPos { row: 4, col: 7 };
+    // two tiles off the way to the bin
+    const NEAR_FIRE: GridPos = GridPos { row: 2, col: 5 };
+    // known, but far from the way to the bin
+    const FAR_FIRE: GridPos = GridPos { row: 7, col: 1 };
+
+    fn burning_world() -> TestWorld {
+        TestWorld::grass(9, 9)
+            .spawn_at(SPAWN)
+            .with_content(BIN, Content::Bin(0..10))
+            .with_content(NEAR_FIRE, Content::Fire)
+            .with_content(FAR_FIRE, Content::Fire)
+    }
+
+    #[test]
+    fn refresh_lists_every_known_fire() {
+        burning_world().run(test_bot().with_firefighting(12), |bot, world| {
+            bot.refresh_fires(world);
+            assert!(bot.firefighting.get_fires().is_empty());
+
+            bot.lssf_update(world, Some(7));
+            bot.refresh_fires(world);
+            let mut fires = bot.firefighting.get_fires().to_vec();
+            fires.sort();
+            assert_eq!(fires, vec![NEAR_FIRE, FAR_FIRE]);
+        });
+    }
+
+    #[test]
+    fn only_fires_near_the_way_to_the_bin_are_threats() {
+        burning_world().run(test_bot().with_firefighting(12), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            bot.refresh_fires(world);
+            assert_eq!(bot.threatening_fires(world), vec![NEAR_FIRE]);
+
+            bot.blacklist
+                .ban(NEAR_FIRE, BlacklistReason::StillBurning, bot.tick);
+            assert!(bot.threatening_fires(world).is_empty());
+        });
+    }
+
+    #[test]
+    fn water_is_fetched_before_the_fire_is_put_out() {
+        burning_world()
+            .with_content(GridPos::new(5, 4), Content::Water(3))
+            .run(test_bot().with_firefighting(12), |bot, world| {
+                bot.lssf_update(world, Some(7));
+
+                // no water yet, the first run goes to get some
+                assert!(bot.routine_fight_fire(world).is_ok());
+                assert_eq!(bot.metrics.fires_extinguished, 0);
+                assert!(bot.carried_quantity(&Content::Water(0)) >= 1);
+
+                assert!(matches!(
+                    bot.routine_fight_fire(world),
+                    Ok(RoutineResult::Success)
+                ));
+                assert_eq!(bot.metrics.fires_extinguished, 1);
+                assert!(!bot.firefighting.get_fires().contains(&NEAR_FIRE));
+            });
+    }
+
+    #[test]
+    fn disabled_firefighting_leaves_fires_alone() {
+        burning_world().run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            assert!(matches!(
+                bot.routine_fight_fire(world),
+                Ok(RoutineResult::NoChanges)
+            ));
+            assert!(bot.firefighting.get_fires().is_empty());
+        });
+    }
+
+    #[test]
+    fn fires_come_before_lower_priority_resources_only() {
+        let garbage = ResourceRule::new(Content::Garbage(0), 10, vec![]);
+        let coins = ResourceRule::new(Content::Coin(0), 15, vec![]);
+        let tied = ResourceRule::new(Content::Water(0), 12, vec![]);
+
+        let bot = test_bot().with_firefighting(12);
+        assert!(bot.fires_come_before(&garbage));
+        assert!(bot.fires_come_before(&tied));
+        assert!(!bot.fires_come_before(&coins));
+        assert!(!test_bot().fires_come_before(&garbage));
+    }
+}
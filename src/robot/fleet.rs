@@ -0,0 +1,441 @@
+//! In-process coordination between several Scrapbots.
+//!
+//! Every bot of a [`Fleet`] gets a [`FleetMember`] handle to the same
+//! [`Blackboard`]. Bots publish the piles and storages they find, claim a
+//! pile before walking to it so that no two bots chase the same one, and split
+//! the map in sectors to explore. Each bot can run in its own `Runner`, on its
+//! own copy of the generated world.
+//!
+//! Claims don't last forever: the fleet clock is the latest tick any member
+//! reported, a claim expires [`CLAIM_TIMEOUT`] ticks after it was taken or
+//! renewed, so piles held by a bot that stopped ticking go back to the others.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use robotics_lib::world::tile::Content;
+
+use crate::robot::{
+    auction::{Assignment, Auction},
+    collection::{content_quantity, same_kind, with_quantity},
+    grid::GridPos,
+    resources::ResourceRule,
+    Scrapbot,
+};
+
+pub type BotId = usize;
+
+/// Fleet ticks a claim lasts unless its owner claims the target again.
+pub const CLAIM_TIMEOUT: usize = 20;
+
+/// Rectangle of the map, `start` included and `end` excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sector {
+    pub row_start: usize,
+    pub row_end: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+}
+
+impl Sector {
+    pub fn contains(&self, pos: GridPos) -> bool {
+        (self.row_start..self.row_end).contains(&pos.row)
+            && (self.col_start..self.col_end).contains(&pos.col)
+    }
+
+    pub fn center(&self) -> GridPos {
+        GridPos::new(
+            (self.row_start + self.row_end) / 2,
+            (self.col_start + self.col_end) / 2,
+        )
+    }
+}
+
+/// Everything the bots of a fleet share.
+#[derive(Debug, Default)]
+pub struct Blackboard {
+    members: Vec<BotId>,
+    next_id: BotId,
+    // latest tick reported by a member
+    clock: usize,
+    piles: HashMap<GridPos, Content>,
+    storages: HashMap<GridPos, Content>,
+    pub(crate) claims: HashMap<GridPos, BotId>,
+    // fleet tick at which each claim expires
+    pub(crate) claim_deadlines: HashMap<GridPos, usize>,
+    sectors: HashMap<BotId, Sector>,
+    pub(crate) auctions: HashMap<GridPos, Auction>,
+    pub(crate) assignments: Vec<Assignment>,
+    // piles whose auction closed without a winner, not announced again
+    pub(crate) unassigned: HashSet<GridPos>,
+}
+
+impl Blackboard {
+    /// What lies on each published pile, quantity included.
+    pub fn get_piles(&self) -> &HashMap<GridPos, Content> {
+        &self.piles
+    }
+
+    pub fn get_storages(&self) -> &HashMap<GridPos, Content> {
+        &self.storages
+    }
+
+    pub fn get_claims(&self) -> &HashMap<GridPos, BotId> {
+        &self.claims
+    }
+
+    pub fn get_assignments(&self) -> &[Assignment] {
+        &self.assignments
+    }
+
+    /// Piles nobody could take when they were auctioned, first come first
+    /// served from then on.
+    pub fn get_unassigned(&self) -> &HashSet<GridPos> {
+        &self.unassigned
+    }
+
+    pub fn member_count(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn get_clock(&self) -> usize {
+        self.clock
+    }
+
+    /// Gives `coords` to `bot` until the fleet clock reaches the deadline.
+    pub(crate) fn insert_claim(&mut self, coords: GridPos, bot: BotId) {
+        self.claims.insert(coords, bot);
+        self.claim_deadlines
+            .insert(coords, self.clock + CLAIM_TIMEOUT);
+    }
+
+    pub(crate) fn remove_claim(&mut self, coords: GridPos) {
+        self.claims.remove(&coords);
+        self.claim_deadlines.remove(&coords);
+    }
+
+    /// Moves the fleet clock to `tick` if it's ahead and drops the claims
+    /// that expired.
+    pub(crate) fn advance_clock(&mut self, tick: usize) {
+        self.clock = self.clock.max(tick);
+        let clock = self.clock;
+        let expired: Vec<GridPos> = self
+            .claim_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= clock)
+            .map(|(coords, _)| *coords)
+            .collect();
+        for coords in expired {
+            self.remove_claim(coords);
+        }
+    }
+
+    pub fn get_sector(&self, bot: BotId) -> Option<Sector> {
+        self.sectors.get(&bot).copied()
+    }
+
+    /// Splits the map in vertical stripes, one per member.
+    fn split_sectors(&mut self, rows: usize, cols: usize) {
+        let members = self.members.len().max(1);
+        let width = cols.div_ceil(members).max(1);
+        self.sectors.clear();
+        for (i, bot) in self.members.iter().enumerate() {
+            let col_start = (i * width).min(cols);
+            self.sectors.insert(
+                *bot,
+                Sector {
+                    row_start: 0,
+                    row_end: rows,
+                    col_start,
+                    col_end: (col_start + width).min(cols),
+                },
+            );
+        }
+    }
+}
+
+/// Shared blackboard, clone it freely.
+#[derive(Debug, Clone, Default)]
+pub struct Fleet {
+    board: Arc<Mutex<Blackboard>>,
+}
+
+impl Fleet {
+    pub fn new() -> Fleet {
+        Fleet::default()
+    }
+
+    /// Registers a new bot, sectors are split again on the next request.
+    pub fn join(&self) -> FleetMember {
+        let mut board = self.board.lock().unwrap();
+        let id = board.next_id;
+        board.next_id += 1;
+        board.members.push(id);
+        board.sectors.clear();
+        FleetMember {
+            id,
+            board: Arc::clone(&self.board),
+        }
+    }
+
+    pub fn board(&self) -> MutexGuard<'_, Blackboard> {
+        self.board.lock().unwrap()
+    }
+
+    /// Every auction closed so far, in closing order.
+    pub fn assignment_log(&self) -> Vec<Assignment> {
+        self.board().get_assignments().to_vec()
+    }
+}
+
+/// Handle a single bot uses to talk to the fleet.
+#[derive(Debug, Clone)]
+pub struct FleetMember {
+    id: BotId,
+    board: Arc<Mutex<Blackboard>>,
+}
+
+impl FleetMember {
+    pub fn get_id(&self) -> BotId {
+        self.id
+    }
+
+    pub fn board(&self) -> MutexGuard<'_, Blackboard> {
+        self.board.lock().unwrap()
+    }
+
+    /// Publishes a pile, an empty content removes it from the board.
+    pub fn publish_pile(&self, coords: GridPos, content: Content) {
+        let mut board = self.board();
+        if content_quantity(&content) == 0 {
+            board.piles.remove(&coords);
+            board.remove_claim(coords);
+            board.unassigned.remove(&coords);
+        } else {
+            board.piles.insert(coords, content);
+        }
+    }
+
+    pub fn publish_storage(&self, coords: GridPos, storage: Content) {
+        self.board().storages.insert(coords, storage);
+    }
+
+    /// Claims a target, returns false if another bot already did or the
+    /// target is still being auctioned. Claiming our own target again
+    /// renews it.
+    pub fn claim(&self, coords: GridPos) -> bool {
+        let mut board = self.board();
+        if board.auctions.contains_key(&coords) {
+            return false;
+        }
+        match board.claims.get(&coords) {
+            Some(owner) if *owner != self.id => false,
+            _ => {
+                board.insert_claim(coords, self.id);
+                true
+            }
+        }
+    }
+
+    pub fn release(&self, coords: GridPos) {
+        let mut board = self.board();
+        if board.claims.get(&coords) == Some(&self.id) {
+            board.remove_claim(coords);
+        }
+    }
+
+    pub fn release_all(&self) {
+        let mut board = self.board();
+        let owned: Vec<GridPos> = board
+            .claims
+            .iter()
+            .filter(|(_, owner)| **owner == self.id)
+            .map(|(coords, _)| *coords)
+            .collect();
+        for coords in owned {
+            board.remove_claim(coords);
+        }
+    }
+
+    /// Leaves the fleet: our claims go back to the others, auctions stop
+    /// waiting for our bids and the map is split again without us.
+    pub fn leave(&self) {
+        self.release_all();
+        let mut board = self.board();
+        board.members.retain(|member| *member != self.id);
+        board.sectors.clear();
+    }
+
+    pub fn is_claimed_by_other(&self, coords: GridPos) -> bool {
+        matches!(self.board().claims.get(&coords), Some(owner) if *owner != self.id)
+    }
+
+    /// Sector this bot should explore, the map is split on first request.
+    pub fn sector(&self, rows: usize, cols: usize) -> Option<Sector> {
+        let mut board = self.board();
+        if board.sectors.is_empty() {
+            board.split_sectors(rows, cols);
+        }
+        board.get_sector(self.id)
+    }
+}
+
+impl Scrapbot {
+    pub fn with_fleet(mut self, member: FleetMember) -> Scrapbot {
+        self.fleet = Some(member);
+        self
+    }
+
+    /// Shares the piles of `content` we found and brings in the ones the
+    /// other bots found, minus the piles they have claimed.
+    pub(crate) fn sync_piles_with_fleet(&self, content: &Content, piles: &mut Vec<GridPos>) {
+        if let Some(fleet) = &self.fleet {
+            for coords in piles.iter() {
+                let quantity = self.piles.get(coords).copied().unwrap_or(1);
+                fleet.publish_pile(*coords, with_quantity(content, quantity));
+                // let the fleet decide who goes there, once
+                fleet.announce(*coords, self.tick);
+            }
+            for (coords, pile) in fleet.board().get_piles() {
+                if same_kind(pile, content) && !piles.contains(coords) {
+                    piles.push(*coords);
+                }
+            }
+            piles.retain(|coords| !fleet.is_claimed_by_other(*coords));
+        }
+    }
+
+    /// Shares the storages we know and brings in the ones the other bots know
+    /// that accept `rule.content`.
+    pub(crate) fn sync_storages_with_fleet(
+        &self,
+        rule: &ResourceRule,
+        storages: &mut Vec<(GridPos, Content)>,
+    ) {
+        if let Some(fleet) = &self.fleet {
+            for (coords, storage) in storages.iter() {
+                fleet.publish_storage(*coords, storage.clone());
+            }
+            for (coords, storage) in fleet.board().get_storages() {
+                let accepted = rule.storage.iter().any(|kind| same_kind(kind, storage));
+                if accepted && !storages.iter().any(|(known, _)| known == coords) {
+                    storages.push((*coords, storage.clone()));
+                }
+            }
+        }
+    }
+
+    /// Claims a target for this bot, always succeeds without a fleet.
+    pub(crate) fn claim_target(&self, coords: GridPos) -> bool {
+        match &self.fleet {
+            Some(fleet) => fleet.claim(coords),
+            None => true,
+        }
+    }
+
+    pub(crate) fn release_target(&self, coords: GridPos) {
+        if let Some(fleet) = &self.fleet {
+            fleet.release(coords);
+        }
+    }
+
+    /// Exploration sector of this bot, the whole map without a fleet.
+    pub(crate) fn exploration_sector(&self, rows: usize, cols: usize) -> Sector {
+        self.fleet
+            .as_ref()
+            .and_then(|fleet| fleet.sector(rows, cols))
+            .unwrap_or(Sector {
+                row_start: 0,
+                row_end: rows,
+                col_start: 0,
+                col_end: cols,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxagworldgenerator::world_generator::{
+        presets::content_presets::OxAgContentPresets,
+        world_generator_builder::OxAgWorldGeneratorBuilder,
+    };
+    use robotics_lib::runner::Runner;
+
+    use super::*;
+
+    #[test]
+    fn sectors_cover_the_map() {
+        let fleet = Fleet::new();
+        let members: Vec<FleetMember> = (0..3).map(|_| fleet.join()).collect();
+        let sectors: Vec<Sector> = members
+            .iter()
+            .map(|member| member.sector(10, 10).unwrap())
+            .collect();
+
+        assert_eq!(sectors[0].col_start, 0);
+        assert_eq!(sectors[2].col_end, 10);
+        for pair in sectors.windows(2) {
+            assert_eq!(pair[0].col_end, pair[1].col_start);
+        }
+    }
+
+    #[test]
+    fn released_pile_can_be_claimed_by_another_bot() {
+        let fleet = Fleet::new();
+        let first = fleet.join();
+        let second = fleet.join();
+        let pile = GridPos::new(2, 2);
+
+        assert!(first.claim(pile));
+        assert!(second.is_claimed_by_other(pile));
+        assert!(!second.claim(pile));
+        first.release(pile);
+        assert!(second.claim(pile));
+    }
+
+    #[test]
+    fn runners_share_the_assignment_log() {
+        let fleet = Fleet::new();
+        let mut runners = vec![];
+        for _ in 0..2 {
+            let mut generator = OxAgWorldGeneratorBuilder::new()
+                .set_seed(42)
+                .set_size(64)
+                .set_content_options_from_preset(OxAgContentPresets::Default)
+                .build()
+                .unwrap();
+            let bot = Scrapbot::headless().with_fleet(fleet.join());
+            runners.push(Runner::new(Box::new(bot), &mut generator).unwrap());
+        }
+
+        for _ in 0..40 {
+            for runner in runners.iter_mut() {
+                runner.game_tick().unwrap();
+            }
+        }
+
+        let log = fleet.assignment_log();
+        assert!(!log.is_empty());
+        for assignment in &log {
+            assert!(assignment.bids.iter().all(|bid| bid.bot < 2));
+            let Some(winner) = assignment.winner else {
+                continue;
+            };
+            // the claim went to the cheapest bidder
+            let winning = assignment
+                .bids
+                .iter()
+                .find(|bid| bid.bot == winner)
+                .unwrap();
+            assert!(winning.path_cost.is_some() && winning.spare_capacity > 0);
+            for bid in &assignment.bids {
+                if bid.spare_capacity > 0 {
+                    if let Some(cost) = bid.path_cost {
+                        assert!(winning.path_cost.unwrap() <= cost);
+                    }
+                }
+            }
+        }
+        assert!(fleet.board().get_claims().values().all(|bot| *bot < 2));
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+
+use robotics_lib::interface::Direction;
+use robotics_lib::world::coordinates::Coordinate;
+use sense_and_find_by_rustafariani::Action;
+
+/// The four directions, in the order neighbours are visited.
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// A tile of the map, always row first.
+///
+/// Every `(usize, usize)` coming from the libraries goes through one of the
+/// conversions below, so the row/col order is decided in one place only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct GridPos {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl GridPos {
+    pub const fn new(row: usize, col: usize) -> GridPos {
+        GridPos { row, col }
+    }
+
+    /// Tile reached moving towards `direction`, `None` past row or col 0.
+    /// The other borders depend on the map, see [`GridPos::is_within`].
+    pub fn step(self, direction: &Direction) -> Option<GridPos> {
+        Some(match direction {
+            Direction::Up => GridPos::new(self.row.checked_sub(1)?, self.col),
+            Direction::Down => GridPos::new(self.row + 1, self.col),
+            Direction::Left => GridPos::new(self.row, self.col.checked_sub(1)?),
+            Direction::Right => GridPos::new(self.row, self.col + 1),
+        })
+    }
+
+    /// Tile reached by an LSSF action, teleports included.
+    pub fn step_action(self, action: &Action) -> Option<GridPos> {
+        match action {
+            Action::Teleport(row, col) => Some(GridPos::from_lssf((*row, *col))),
+            action => self.step(&action_to_direction(action)?),
+        }
+    }
+
+    /// Neighbours inside a `rows` x `cols` map, with the direction leading
+    /// to each of them.
+    pub fn neighbours(
+        self,
+        rows: usize,
+        cols: usize,
+    ) -> impl Iterator<Item = (Direction, GridPos)> {
+        DIRECTIONS.into_iter().filter_map(move |direction| {
+            self.step(&direction)
+                .filter(|next| next.is_within(rows, cols))
+                .map(|next| (direction, next))
+        })
+    }
+
+    pub fn is_within(self, rows: usize, cols: usize) -> bool {
+        self.row < rows && self.col < cols
+    }
+
+    pub fn manhattan(self, other: GridPos) -> usize {
+        self.row.abs_diff(other.row) + self.col.abs_diff(other.col)
+    }
+
+    pub fn chebyshev(self, other: GridPos) -> usize {
+        self.row
+            .abs_diff(other.row)
+            .max(self.col.abs_diff(other.col))
+    }
+
+    /// Direction to face `other`, `None` unless it's one step away.
+    pub fn direction_to(self, other: GridPos) -> Option<Direction> {
+        DIRECTIONS
+            .into_iter()
+            .find(|direction| self.step(direction) == Some(other))
+    }
+
+    /// LSSF tuples are `(row, col)`.
+    pub fn from_lssf((row, col): (usize, usize)) -> GridPos {
+        GridPos::new(row, col)
+    }
+
+    pub fn to_lssf(self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+}
+
+impl fmt::Display for GridPos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.row, self.col)
+    }
+}
+
+impl From<(usize, usize)> for GridPos {
+    fn from((row, col): (usize, usize)) -> Self {
+        GridPos::new(row, col)
+    }
+}
+
+impl From<GridPos> for (usize, usize) {
+    fn from(pos: GridPos) -> Self {
+        (pos.row, pos.col)
+    }
+}
+
+impl From<&Coordinate> for GridPos {
+    fn from(coordinate: &Coordinate) -> Self {
+        GridPos::new(coordinate.get_row(), coordinate.get_col())
+    }
+}
+
+impl From<GridPos> for Coordinate {
+    fn from(pos: GridPos) -> Self {
+        Coordinate::new(pos.row, pos.col)
+    }
+}
+
+pub fn direction_to_action(direction: &Direction) -> Action {
+    match direction {
+        Direction::Up => Action::North,
+        Direction::Down => Action::South,
+        Direction::Right => Action::East,
+        Direction::Left => Action::West,
+    }
+}
+
+/// `None` for teleports, they don't have a direction.
+pub fn action_to_direction(action: &Action) -> Option<Direction> {
+    match action {
+        Action::North => Some(Direction::Up),
+        Action::South => Some(Direction::Down),
+        Action::East => Some(Direction::Right),
+        Action::West => Some(Direction::Left),
+        Action::Teleport(..) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coordinates_convert_both_ways() {
+        let pos = GridPos::new(3, 7);
+        let coordinate: Coordinate = pos.into();
+        assert_eq!((coordinate.get_row(), coordinate.get_col()), (3, 7));
+        assert_eq!(GridPos::from(&coordinate), pos);
+    }
+
+    fn neighbours_of(pos: GridPos, rows: usize, cols: usize) -> Vec<GridPos> {
+        pos.neighbours(rows, cols).map(|(_, next)| next).collect()
+    }
+
+    #[test]
+    fn step_stops_at_row_and_col_zero_only() {
+        let corner = GridPos::new(0, 0);
+        assert_eq!(corner.step(&Direction::Up), None);
+        assert_eq!(corner.step(&Direction::Left), None);
+        assert_eq!(corner.step(&Direction::Down), Some(GridPos::new(1, 0)));
+        assert_eq!(corner.step(&Direction::Right), Some(GridPos::new(0, 1)));
+        // the far borders are up to the map
+        assert_eq!(
+            GridPos::new(4, 4).step(&Direction::Down),
+            Some(GridPos::new(5, 4))
+        );
+    }
+
+    #[test]
+    fn neighbours_stay_inside_the_map() {
+        assert_eq!(
+            neighbours_of(GridPos::new(0, 0), 3, 3),
+            vec![GridPos::new(1, 0), GridPos::new(0, 1)]
+        );
+        assert_eq!(
+            neighbours_of(GridPos::new(2, 2), 3, 3),
+            vec![GridPos::new(1, 2), GridPos::new(2, 1)]
+        );
+        assert_eq!(
+            neighbours_of(GridPos::new(0, 1), 3, 3),
+            vec![GridPos::new(1, 1), GridPos::new(0, 0), GridPos::new(0, 2)]
+        );
+        assert_eq!(neighbours_of(GridPos::new(1, 1), 3, 3).len(), 4);
+        // single row and single tile maps
+        assert_eq!(
+            neighbours_of(GridPos::new(0, 1), 1, 3),
+            vec![GridPos::new(0, 0), GridPos::new(0, 2)]
+        );
+        assert!(neighbours_of(GridPos::new(0, 0), 1, 1).is_empty());
+    }
+
+    #[test]
+    fn neighbours_come_with_the_direction_to_them() {
+        let pos = GridPos::new(1, 1);
+        for (direction, next) in pos.neighbours(3, 3) {
+            assert_eq!(pos.step(&direction), Some(next));
+            assert!(
+                matches!(pos.direction_to(next), Some(found) if pos.step(&found) == Some(next))
+            );
+        }
+    }
+
+    #[test]
+    fn direction_to_needs_a_single_step() {
+        let pos = GridPos::new(0, 0);
+        assert!(matches!(
+            pos.direction_to(GridPos::new(1, 0)),
+            Some(Direction::Down)
+        ));
+        assert!(matches!(
+            pos.direction_to(GridPos::new(0, 1)),
+            Some(Direction::Right)
+        ));
+        assert!(pos.direction_to(pos).is_none());
+        assert!(pos.direction_to(GridPos::new(1, 1)).is_none());
+        assert!(pos.direction_to(GridPos::new(0, 2)).is_none());
+    }
+
+    #[test]
+    fn manhattan_is_symmetric_and_counts_both_axes() {
+        let a = GridPos::new(2, 7);
+        let b = GridPos::new(5, 3);
+        assert_eq!(a.manhattan(b), 7);
+        assert_eq!(b.manhattan(a), 7);
+        assert_eq!(a.manhattan(a), 0);
+        assert_eq!(GridPos::new(0, 0).manhattan(GridPos::new(0, 9)), 9);
+        assert_eq!(a.chebyshev(b), 4);
+    }
+}
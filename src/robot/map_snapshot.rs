@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use robotics_lib::interface::robot_map;
+use robotics_lib::world::tile::Tile;
+use robotics_lib::world::World;
+
+use crate::robot::{grid::GridPos, Scrapbot};
+
+pub type KnownMap = Vec<Vec<Option<Tile>>>;
+
+/// Rows and columns of the map.
+pub fn map_dims(known_map: &KnownMap) -> (usize, usize) {
+    (
+        known_map.len(),
+        known_map.first().map_or(0, |tiles| tiles.len()),
+    )
+}
+
+/// The tile at `pos`, `None` if we don't know it or it's outside the map.
+pub fn tile_at(known_map: &KnownMap, pos: GridPos) -> Option<&Tile> {
+    known_map.get(pos.row)?.get(pos.col)?.as_ref()
+}
+
+/// How many tiles of the map we know.
+pub fn known_tiles(known_map: &KnownMap) -> usize {
+    known_map
+        .iter()
+        .flatten()
+        .filter(|tile| tile.is_some())
+        .count()
+}
+
+/// How many tiles we know in the square of side `diameter` around `center`.
+pub fn known_tiles_around(known_map: &KnownMap, center: GridPos, diameter: usize) -> usize {
+    let radius = diameter / 2;
+    let clamp = |center: usize, len: usize| {
+        center.saturating_sub(radius).min(len)..(center + radius + 1).min(len)
+    };
+    known_map[clamp(center.row, known_map.len())]
+        .iter()
+        .map(|tiles| {
+            tiles[clamp(center.col, tiles.len())]
+                .iter()
+                .filter(|tile| tile.is_some())
+                .count()
+        })
+        .sum()
+}
+
+/// Copy of the known map shared by everything that runs during a tick.
+///
+/// `robot_map` clones the whole map on every call, which adds up quickly on
+/// big worlds. The snapshot is taken on first use and kept until something
+/// that can change the known map happens (a scan, a content update, a move we
+/// didn't make). Our own steps patch the tiles they discover instead. The
+/// world dimensions never change, so they're kept forever.
+///
+/// The number of known tiles is kept up to date the same way: steps and
+/// scans add what they discovered, it's only counted again from the whole
+/// map after a move we didn't make.
+#[derive(Debug, Default)]
+pub struct MapSnapshot {
+    dims: Option<(usize, usize)>,
+    tiles: Option<Arc<KnownMap>>,
+    refreshes: usize,
+    // tiles in the known map, None until counted again
+    known: Option<usize>,
+    // set while one of our steps runs, its move event keeps the snapshot
+    own_move: bool,
+}
+
+impl MapSnapshot {
+    pub fn invalidate(&mut self) {
+        self.tiles = None;
+    }
+
+    /// The next move event comes from a step that patches the snapshot
+    /// itself.
+    pub fn expect_own_move(&mut self) {
+        self.own_move = true;
+    }
+
+    /// The step failed, no move event is coming.
+    pub fn forget_own_move(&mut self) {
+        self.own_move = false;
+    }
+
+    /// Moves made by someone else (tools, teleports) drop the snapshot.
+    pub fn on_moved(&mut self) {
+        if !std::mem::take(&mut self.own_move) {
+            self.invalidate();
+            self.known = None;
+        }
+    }
+
+    /// `tiles` were discovered by a scan.
+    pub fn add_known(&mut self, tiles: usize) {
+        if let Some(known) = self.known.as_mut() {
+            *known += tiles;
+        }
+    }
+
+    /// Writes the 3x3 `view` around `center` returned by a step. The snapshot
+    /// is dropped instead if somebody still holds it.
+    pub fn patch(&mut self, center: GridPos, view: &[Vec<Option<Tile>>]) {
+        self.own_move = false;
+        let Some(tiles) = self.tiles.as_mut() else {
+            self.known = None;
+            return;
+        };
+        let Some(tiles) = Arc::get_mut(tiles) else {
+            self.invalidate();
+            self.known = None;
+            return;
+        };
+        let mut discovered = 0;
+        for (i, row) in view.iter().enumerate() {
+            for (j, tile) in row.iter().enumerate() {
+                let (Some(tile), Some(r), Some(c)) = (
+                    tile,
+                    (center.row + i).checked_sub(1),
+                    (center.col + j).checked_sub(1),
+                ) else {
+                    continue;
+                };
+                if let Some(known) = tiles.get_mut(r).and_then(|tiles| tiles.get_mut(c)) {
+                    discovered += usize::from(known.is_none());
+                    *known = Some(tile.clone());
+                }
+            }
+        }
+        self.add_known(discovered);
+    }
+
+    /// How many times the map has been copied from the world.
+    pub fn get_refreshes(&self) -> usize {
+        self.refreshes
+    }
+}
+
+impl Scrapbot {
+    /// The known map, copied from the world only if it changed since the
+    /// last call.
+    pub(crate) fn known_map(&self, world: &World) -> Arc<KnownMap> {
+        let mut snapshot = self.map_snapshot.borrow_mut();
+        if snapshot.tiles.is_none() {
+            let map = match robot_map(world) {
+                Some(map) => {
+                    snapshot.dims.get_or_insert(map_dims(&map));
+                    map
+                }
+                // nothing to learn the dimensions from yet
+                None => vec![],
+            };
+            snapshot.tiles = Some(Arc::new(map));
+            snapshot.refreshes += 1;
+        }
+        Arc::clone(snapshot.tiles.as_ref().unwrap())
+    }
+
+    /// How many tiles of the map we know, counted from the whole map only
+    /// when the running count was lost.
+    pub(crate) fn known_tile_count(&self, world: &World) -> usize {
+        let known_map = self.known_map(world);
+        let mut snapshot = self.map_snapshot.borrow_mut();
+        *snapshot
+            .known
+            .get_or_insert_with(|| known_tiles(&known_map))
+    }
+
+    /// Rows and columns of the world, which doesn't have to be square.
+    pub(crate) fn world_dims(&self, world: &World) -> (usize, usize) {
+        let dims = self.map_snapshot.borrow().dims;
+        match dims {
+            Some(dims) => dims,
+            None => map_dims(&self.known_map(world)),
+        }
+    }
+
+    pub(crate) fn invalidate_known_map(&self) {
+        self.map_snapshot.borrow_mut().invalidate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::world::tile::{Content, TileType};
+
+    use super::*;
+
+    fn tile(tile_type: TileType) -> Tile {
+        Tile {
+            tile_type,
+            content: Content::None,
+            elevation: 0,
+        }
+    }
+
+    fn snapshot(map: KnownMap) -> MapSnapshot {
+        MapSnapshot {
+            dims: Some(map_dims(&map)),
+            tiles: Some(Arc::new(map)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn own_move_patches_the_view() {
+        let mut snapshot = snapshot(vec![vec![None; 4]; 3]);
+        snapshot.expect_own_move();
+        snapshot.on_moved();
+        let view = vec![
+            vec![None, Some(tile(TileType::Grass)), None],
+            vec![None, Some(tile(TileType::Sand)), Some(tile(TileType::Hill))],
+            vec![None, None, None],
+        ];
+        // the view sticks out of the top left corner
+        snapshot.patch(GridPos::new(0, 0), &view);
+
+        let tiles = snapshot.tiles.as_ref().unwrap();
+        assert_eq!(tiles[0][0].as_ref().unwrap().tile_type, TileType::Sand);
+        assert_eq!(tiles[0][1].as_ref().unwrap().tile_type, TileType::Hill);
+        assert_eq!(known_tiles(tiles), 2);
+        assert_eq!(snapshot.get_refreshes(), 0);
+    }
+
+    #[test]
+    fn foreign_move_drops_the_snapshot() {
+        let mut snapshot = snapshot(vec![vec![None; 2]; 2]);
+        snapshot.on_moved();
+        assert!(snapshot.tiles.is_none());
+        assert_eq!(snapshot.dims, Some((2, 2)));
+    }
+
+    #[test]
+    fn shared_snapshot_is_dropped_instead_of_patched() {
+        let mut snapshot = snapshot(vec![vec![None; 3]; 3]);
+        let held = Arc::clone(snapshot.tiles.as_ref().unwrap());
+        snapshot.expect_own_move();
+        snapshot.patch(GridPos::new(1, 1), &[vec![Some(tile(TileType::Grass))]]);
+        assert!(snapshot.tiles.is_none());
+        assert_eq!(known_tiles(&held), 0);
+    }
+
+    #[test]
+    fn known_tiles_around_stay_inside_the_map() {
+        let mut map: KnownMap = vec![vec![None; 5]; 5];
+        for (row, col) in [(0, 0), (1, 1), (2, 2), (4, 4), (0, 4)] {
+            map[row][col] = Some(tile(TileType::Grass));
+        }
+        assert_eq!(known_tiles_around(&map, GridPos::new(1, 1), 3), 3);
+        assert_eq!(known_tiles_around(&map, GridPos::new(0, 0), 5), 3);
+        assert_eq!(known_tiles_around(&map, GridPos::new(4, 4), 1), 1);
+        assert_eq!(
+            known_tiles_around(&map, GridPos::new(2, 2), 9),
+            known_tiles(&map)
+        );
+    }
+
+    #[test]
+    fn patches_count_only_new_tiles() {
+        let mut map: KnownMap = vec![vec![None; 3]; 3];
+        map[1][1] = Some(tile(TileType::Grass));
+        let mut snapshot = snapshot(map);
+        snapshot.known = Some(1);
+
+        let view = vec![vec![Some(tile(TileType::Grass)); 3]; 3];
+        snapshot.expect_own_move();
+        snapshot.patch(GridPos::new(1, 1), &view);
+        assert_eq!(snapshot.known, Some(9));
+        snapshot.add_known(2);
+        assert_eq!(snapshot.known, Some(11));
+
+        // a move we didn't make loses the count
+        snapshot.on_moved();
+        assert_eq!(snapshot.known, None);
+    }
+}
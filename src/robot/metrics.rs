@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use robotics_lib::event::events::Event;
+use robotics_lib::world::tile::Content;
+
+use crate::robot::{collection::same_kind, grid::GridPos, Scrapbot};
+
+/// Samples and scans a bot keeps until they're published, the oldest ones
+/// are dropped first.
+pub const SERIES_CAPACITY: usize = 1024;
+
+/// Snapshot of the counters at the end of a tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TickSample {
+    pub tick: usize,
+    pub garbage_collected: usize,
+    pub garbage_deposited: usize,
+    pub distance_walked: usize,
+    pub energy_consumed: usize,
+    pub tiles_discovered: usize,
+    pub energy_level: usize,
+}
+
+/// What one LSSF scan brought back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanYield {
+    pub tick: usize,
+    pub diameter: usize,
+    /// Tiles we didn't know before the scan.
+    pub new_tiles: usize,
+    /// Garbage piles known inside the scanned area.
+    pub targets: usize,
+}
+
+/// Counters updated while the bot runs, see [`Scrapbot::metrics`].
+///
+/// [`Scrapbot::metrics`]: crate::robot::Scrapbot::metrics
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub ticks: usize,
+    pub garbage_collected: usize,
+    pub garbage_deposited: usize,
+    pub distance_walked: usize,
+    pub teleports: usize,
+    /// Walking plans replaced by a cheaper teleport trip.
+    pub teleport_shortcuts: usize,
+    pub lssf_scans: usize,
+    /// Scans avoided because the area was already up to date.
+    pub lssf_scans_skipped: usize,
+    /// Time spent inside LSSF sensing.
+    pub lssf_scan_time: Duration,
+    /// Tiles first seen by an LSSF scan.
+    pub lssf_tiles_discovered: usize,
+    pub spyglass_scans: usize,
+    pub failed_moves: usize,
+    /// Steps refused because they led onto a hazard or an unknown tile.
+    pub hazards_avoided: usize,
+    /// Trees and rocks destroyed to clear the way.
+    pub obstacles_cleared: usize,
+    pub fires_extinguished: usize,
+    /// Items sold at markets and the coins we got for them.
+    pub items_sold: usize,
+    pub coins_earned: usize,
+    /// Collection tours cut short to keep the energy reserve.
+    pub tours_aborted: usize,
+    /// Ticks spent idle waiting for energy.
+    pub ticks_resting: usize,
+    pub energy_consumed: usize,
+    pub tiles_discovered: usize,
+    /// How many times each routine ended with each outcome.
+    pub routine_outcomes: BTreeMap<(&'static str, &'static str), usize>,
+    /// Samples not published yet, see [`SERIES_CAPACITY`]. A sink gets
+    /// every published sample.
+    pub series: VecDeque<TickSample>,
+    pub scan_yields: VecDeque<ScanYield>,
+    last_position: Option<GridPos>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Updates the counters that can be derived from library events.
+    pub(crate) fn record_event(&mut self, event: &Event) {
+        match event {
+            Event::EnergyConsumed(energy) => self.energy_consumed += energy,
+            Event::Moved(_, coords) => {
+                let position = GridPos::from(*coords);
+                // anything further than one tile away is a teleport
+                match self.last_position {
+                    Some(last) if last.manhattan(position) > 1 => self.teleports += 1,
+                    _ => self.distance_walked += 1,
+                }
+                self.last_position = Some(position);
+            }
+            Event::AddedToBackpack(content, quantity)
+                if same_kind(content, &Content::Garbage(0)) =>
+            {
+                self.garbage_collected += quantity
+            }
+            Event::RemovedFromBackpack(content, quantity)
+                if same_kind(content, &Content::Garbage(0)) =>
+            {
+                self.garbage_deposited += quantity
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn record_routine(&mut self, routine: &'static str, outcome: &'static str) {
+        *self.routine_outcomes.entry((routine, outcome)).or_insert(0) += 1;
+    }
+
+    pub(crate) fn record_scan(&mut self, scan: ScanYield) {
+        self.lssf_tiles_discovered += scan.new_tiles;
+        push_bounded(&mut self.scan_yields, scan);
+    }
+
+    /// Average tiles discovered per LSSF scan.
+    pub fn scan_yield(&self) -> f64 {
+        match self.lssf_scans {
+            0 => 0.0,
+            scans => self.lssf_tiles_discovered as f64 / scans as f64,
+        }
+    }
+
+    pub(crate) fn set_position(&mut self, position: GridPos) {
+        self.last_position = Some(position);
+    }
+
+    /// Closes the tick, storing a sample in the time series.
+    pub(crate) fn end_tick(&mut self, tick: usize, tiles_discovered: usize, energy_level: usize) {
+        self.ticks += 1;
+        self.tiles_discovered = tiles_discovered;
+        let sample = TickSample {
+            tick,
+            garbage_collected: self.garbage_collected,
+            garbage_deposited: self.garbage_deposited,
+            distance_walked: self.distance_walked,
+            energy_consumed: self.energy_consumed,
+            tiles_discovered,
+            energy_level,
+        };
+        push_bounded(&mut self.series, sample);
+    }
+
+    /// Brings `sink` up to date: the counters are copied and the samples
+    /// and scans we kept are moved to the end of its series, so the bot
+    /// never holds more than one tick of them.
+    pub(crate) fn publish_into(&mut self, sink: &mut Metrics) {
+        let series = std::mem::take(&mut self.series);
+        let scan_yields = std::mem::take(&mut self.scan_yields);
+        let mut published = self.clone();
+
+        published.series = std::mem::take(&mut sink.series);
+        published.scan_yields = std::mem::take(&mut sink.scan_yields);
+        published.series.extend(series);
+        published.scan_yields.extend(scan_yields);
+        *sink = published;
+    }
+}
+
+fn push_bounded<T>(buffer: &mut VecDeque<T>, item: T) {
+    if buffer.len() >= SERIES_CAPACITY {
+        buffer.pop_front();
+    }
+    buffer.push_back(item);
+}
+
+impl Scrapbot {
+    /// Copies the metrics into `sink` at the end of every tick, so they can be
+    /// read once the bot has been moved into a `Runner`.
+    pub fn with_metrics_sink(mut self, sink: Arc<Mutex<Metrics>>) -> Scrapbot {
+        self.metrics_sink = Some(sink);
+        self
+    }
+
+    pub(crate) fn publish_metrics(&mut self) {
+        if let Some(sink) = &self.metrics_sink {
+            self.metrics.publish_into(&mut sink.lock().unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishing_moves_the_samples_to_the_sink() {
+        let mut metrics = Metrics::new();
+        let mut sink = Metrics::new();
+        metrics.end_tick(1, 9, 1000);
+        metrics.record_scan(ScanYield {
+            tick: 1,
+            diameter: 5,
+            new_tiles: 9,
+            targets: 0,
+        });
+        metrics.publish_into(&mut sink);
+
+        metrics.garbage_collected = 3;
+        metrics.end_tick(2, 12, 990);
+        metrics.publish_into(&mut sink);
+
+        assert_eq!(sink.ticks, 2);
+        assert_eq!(sink.garbage_collected, 3);
+        assert_eq!(sink.lssf_tiles_discovered, 9);
+        let ticks: Vec<usize> = sink.series.iter().map(|sample| sample.tick).collect();
+        assert_eq!(ticks, vec![1, 2]);
+        assert_eq!(sink.series[1].garbage_collected, 3);
+        assert_eq!(sink.scan_yields.len(), 1);
+        // the bot doesn't keep what it published
+        assert!(metrics.series.is_empty());
+        assert!(metrics.scan_yields.is_empty());
+    }
+
+    #[test]
+    fn unpublished_series_keep_only_the_latest_samples() {
+        let mut metrics = Metrics::new();
+        for tick in 1..=SERIES_CAPACITY + 5 {
+            metrics.end_tick(tick, 0, 1000);
+        }
+        assert_eq!(metrics.series.len(), SERIES_CAPACITY);
+        assert_eq!(metrics.series.front().unwrap().tick, 6);
+        assert_eq!(metrics.ticks, SERIES_CAPACITY + 5);
+    }
+}
@@ -1,37 +1,81 @@
+use std::cell::RefCell;
 use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::mem::Discriminant;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use oxagaudiotool::OxAgAudioTool;
 use robot_for_visualizer::RobotForVisualizer;
 use robotics_lib::energy::Energy;
 use robotics_lib::event::events::Event;
-use robotics_lib::interface::robot_map;
+use robotics_lib::interface::look_at_sky;
 use robotics_lib::runner::backpack::BackPack;
 use robotics_lib::runner::{Robot, Runnable, Runner};
 use robotics_lib::utils::LibError;
 use robotics_lib::world::coordinates::Coordinate;
+use robotics_lib::world::environmental_conditions::EnvironmentalConditions;
 use robotics_lib::world::tile::Content;
-use robotics_lib::world::tile::Content::Garbage;
 use robotics_lib::world::world_generator::Generator;
 use robotics_lib::world::World;
 use sense_and_find_by_rustafariani::{Action, Lssf};
 use spyglass::spyglass::Spyglass;
 
-use crate::robot::sound::{populate_sounds, populate_sounds_given_path};
+use crate::robot::{
+    backpack::BackpackManager,
+    blacklist::TargetBlacklist,
+    cost_model::GoCosts,
+    energy_reserve::{BinCosts, EnergyReserve},
+    firefighting::FirefightingPolicy,
+    fleet::FleetMember,
+    grid::GridPos,
+    map_snapshot::{known_tiles_around, MapSnapshot},
+    metrics::Metrics,
+    obstacles::ObstaclePolicy,
+    recharge::RechargePlanner,
+    resources::ResourceTable,
+    roads::RoadPlanner,
+    scan_cache::ScanCache,
+    scheduling::SchedulingPolicy,
+    sensing::SensingPolicy,
+    sound::{populate_sounds, populate_sounds_given_path},
+    teleports::TeleportNetwork,
+    trading::TradingPolicy,
+};
 
+pub mod auction;
+pub mod backpack;
+pub mod blacklist;
+pub mod collection;
+pub mod cost_model;
+pub mod energy_reserve;
+#[cfg(feature = "prometheus")]
+pub mod exporter;
+pub mod firefighting;
+pub mod fleet;
+pub mod grid;
+pub mod map_snapshot;
+pub mod metrics;
 mod movement;
+pub mod navigation;
+pub mod obstacles;
+pub mod recharge;
+pub mod resources;
+pub mod roads;
 mod routines;
+pub mod scan_cache;
+pub mod scheduling;
+pub mod sensing;
 mod sound;
-mod trash_collection;
+pub mod teleports;
+#[cfg(test)]
+mod test_world;
+pub mod trading;
 
 // Each bin can handle max 10 of garbage.
 // Reborn from the ashes
 
-const MAX_BACKPACK_ITEMS: usize = 20;
-
 pub enum BotAction {
-    Put,
-    Destroy,
     Start,
     Walk,
 }
@@ -39,13 +83,36 @@ pub enum BotAction {
 pub struct Scrapbot {
     pub robot: Robot,
     pub audio: Option<OxAgAudioTool>,
-    pub bin_coords: Option<Vec<(usize, usize)>>,
-    pub trash_coords: Option<Vec<(usize, usize)>>,
     pub lssf: Option<Lssf>,
     pub actions_vec: Option<Vec<Action>>,
     pub bot_action: BotAction,
     pub search_radius: Option<usize>,
     pub quadrants_visited: HashMap<usize, bool>,
+    pub blacklist: TargetBlacklist,
+    pub tick: usize,
+    pub environment: Option<EnvironmentalConditions>,
+    pub go_costs: GoCosts,
+    pub scheduling: SchedulingPolicy,
+    pub piles: HashMap<GridPos, usize>,
+    pub resources: ResourceTable,
+    pub resource_coords: HashMap<Discriminant<Content>, Vec<GridPos>>,
+    pub backpack_manager: BackpackManager,
+    pub fleet: Option<FleetMember>,
+    pub metrics: Metrics,
+    pub metrics_sink: Option<Arc<Mutex<Metrics>>>,
+    pub scan_cache: ScanCache,
+    pub map_snapshot: RefCell<MapSnapshot>,
+    pub teleport_network: TeleportNetwork,
+    pub roads: RoadPlanner,
+    pub obstacles: ObstaclePolicy,
+    pub firefighting: FirefightingPolicy,
+    pub trading: TradingPolicy,
+    pub reserve: EnergyReserve,
+    pub bin_costs: RefCell<BinCosts>,
+    pub recharge: RechargePlanner,
+    pub sensing: SensingPolicy,
+    #[cfg(feature = "prometheus")]
+    pub exporter: Option<exporter::PrometheusExporter>,
 }
 
 impl Default for Scrapbot {
@@ -55,14 +122,22 @@ impl Default for Scrapbot {
 }
 
 impl Scrapbot {
+    /// Needs the sounds directory, see [`RobotForVisualizer::set_audio_path`].
     pub fn new() -> Scrapbot {
         Scrapbot {
-            robot: Robot::new(),
             audio: Some(
                 OxAgAudioTool::new(populate_sounds(), HashMap::new(), HashMap::new()).unwrap(),
             ),
-            bin_coords: None,
-            trash_coords: None,
+            ..Scrapbot::headless()
+        }
+    }
+
+    /// A bot without the audio tool, for tests, benchmarks and fleets: it
+    /// doesn't need the sounds or an audio device.
+    pub fn headless() -> Scrapbot {
+        Scrapbot {
+            robot: Robot::new(),
+            audio: None,
             lssf: Some(Lssf::new()),
             actions_vec: None,
             bot_action: BotAction::Start,
@@ -73,23 +148,45 @@ impl Scrapbot {
                 (3usize, false),
                 (4usize, false),
             ]),
+            blacklist: TargetBlacklist::new(),
+            tick: 0,
+            environment: None,
+            go_costs: GoCosts::default(),
+            scheduling: SchedulingPolicy::default(),
+            piles: HashMap::new(),
+            resources: ResourceTable::default(),
+            resource_coords: HashMap::new(),
+            backpack_manager: BackpackManager::new(),
+            fleet: None,
+            metrics: Metrics::new(),
+            metrics_sink: None,
+            scan_cache: ScanCache::default(),
+            map_snapshot: RefCell::new(MapSnapshot::default()),
+            teleport_network: TeleportNetwork::default(),
+            roads: RoadPlanner::default(),
+            obstacles: ObstaclePolicy::default(),
+            firefighting: FirefightingPolicy::default(),
+            trading: TradingPolicy::default(),
+            reserve: EnergyReserve::default(),
+            bin_costs: RefCell::new(BinCosts::default()),
+            recharge: RechargePlanner::default(),
+            sensing: SensingPolicy::default(),
+            #[cfg(feature = "prometheus")]
+            exporter: None,
         }
     }
 
     // backpack methods
-    pub fn get_remaining_backpack_space(&mut self) -> usize {
-        let used_space: usize = self.robot.backpack.get_contents().values().sum();
-        MAX_BACKPACK_ITEMS - used_space
+    pub fn get_remaining_backpack_space(&self) -> usize {
+        self.free_backpack_space()
     }
 
-    pub fn get_content_quantity(&mut self, content: &Content) -> usize {
-        *self.robot.backpack.get_contents().get(content).unwrap()
+    pub fn get_content_quantity(&self, content: &Content) -> usize {
+        self.carried_quantity(content)
     }
 
-    // energy
-    pub fn full_recharge(&mut self) {
-        *self.get_energy_mut() = Robot::new().energy;
-        self.handle_event(Event::EnergyRecharged(1000));
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
     }
 
     fn round_down_to_nearest_odd(value: usize) -> usize {
@@ -101,11 +198,21 @@ impl Scrapbot {
     }
 
     // map exploration methods
+    /// Explores with the Spyglass, which only knows square maps: on other
+    /// maps we fall back to an LSSF scan.
     pub fn spyglass_explore(&mut self, world: &mut World) {
         //println!("spyglass exploration");
-        let map = robot_map(world).unwrap();
-        let map_size = map.len();
+        let (rows, cols) = self.world_dims(world);
+        // the spyglass only takes square maps, big enough to look at least a
+        // tile away
+        if rows != cols || rows / 4 == 0 {
+            self.lssf_update(world, None);
+            return;
+        }
+        let map_size = rows;
         let distance = if map_size < 64 { map_size / 4 } else { 30 };
+        let center = self.position();
+        let known_before = known_tiles_around(&self.known_map(world), center, 2 * distance + 1);
         let mut spy_glass = Spyglass::new(
             self.get_coordinate().get_row(),
             self.get_coordinate().get_col(),
@@ -117,60 +224,81 @@ impl Scrapbot {
             |_| false,
         );
         spy_glass.new_discover(self, world);
+        self.metrics.spyglass_scans += 1;
+        self.invalidate_known_map();
+        let known_after = known_tiles_around(&self.known_map(world), center, 2 * distance + 1);
+        self.map_snapshot
+            .borrow_mut()
+            .add_known(known_after.saturating_sub(known_before));
     }
 
     pub fn lssf_update(&mut self, world: &mut World, input_radius: Option<usize>) {
-        self.full_recharge();
-        // Use the specified radius if provided, otherwise use default (1/8 of map size)
-        // or the nearest border distance so that the tool doesn't shit itself
+        // Use the specified radius if provided, otherwise the adaptive default
+        // (see SensingPolicy), or the nearest border distance so that the tool
+        // doesn't shit itself
 
-        let world_dim = robot_map(world).unwrap().len();
-        let mut scan_diameter = input_radius.unwrap_or(world_dim / 4);
+        let (rows, cols) = self.world_dims(world);
+        // the smallest scan is 3x3, smaller maps are only known by walking
+        if rows < 3 || cols < 3 {
+            self.metrics.lssf_scans_skipped += 1;
+            return;
+        }
+        let adaptive = input_radius.is_none();
+        let mut scan_diameter = match input_radius {
+            Some(diameter) => diameter,
+            None => self.default_scan_diameter(rows, cols),
+        };
 
         // print!("proposed scan diameter {} | ", scan_diameter);
-        scan_diameter = min(
-            Self::round_down_to_nearest_odd(scan_diameter),
-            Self::round_down_to_nearest_odd(self.nearest_border_distance(world) * 2),
-        );
+        let proposed = Self::round_down_to_nearest_odd(scan_diameter);
+        let border_limit = Self::round_down_to_nearest_odd(self.nearest_border_distance(world) * 2);
+        // a scan cut by the border tells nothing about the radius
+        let adaptive = adaptive && proposed <= border_limit;
+        scan_diameter = min(proposed, border_limit);
+
+        // Nothing changed around us since the last scan, LSSF already knows
+        let center = self.position();
+        if !self.scan_cache.needs_scan(center, scan_diameter) {
+            self.metrics.lssf_scans_skipped += 1;
+            return;
+        }
 
         // Update LSSF
+        let known_before = known_tiles_around(&self.known_map(world), center, scan_diameter);
+        let started = Instant::now();
         let mut lssf = self.lssf.take().unwrap();
         lssf.smart_sensing_centered(scan_diameter, world, self, 0)
             .ok();
+        self.metrics.lssf_scans += 1;
+        self.metrics.lssf_scan_time += started.elapsed();
 
         self.lssf = Some(lssf);
+        self.scan_cache.record_scan(center, scan_diameter);
+        self.invalidate_known_map();
         self.store_tiles(world);
+        self.record_scan_yield(world, center, scan_diameter, known_before, adaptive);
     }
 
-    pub fn util_sort_points_from_nearest(&mut self, content: Content) {
-        // Take the coordinates vector to be ordered based on the content type
-        let mut coords_vec_to_be_ordered = if content == Garbage(0) {
-            self.trash_coords.take()
-        } else {
-            self.bin_coords.take()
-        };
-
-        // If the vector is not empty, sort it by distance from the robot's current position
-        if let Some(coords_vec) = &mut coords_vec_to_be_ordered {
-            coords_vec.sort_by_key(|(row, col)| {
-                // Calculate the absolute differences in row and column
-                let row_diff = (*row as i32 - self.get_coordinate().get_row() as i32).abs();
-                let col_diff = (*col as i32 - self.get_coordinate().get_col() as i32).abs();
-                // Sort by the sum of the absolute differences
-                row_diff + col_diff
-            });
-        }
-
-        // Put back the ordered coordinates vector based on the content type
-        if content == Garbage(0) {
-            self.trash_coords = coords_vec_to_be_ordered;
-        } else {
-            self.bin_coords = coords_vec_to_be_ordered;
-        }
+    /// Sorts the coordinates by the energy needed to reach them, unreachable
+    /// ones last (nearest first among them).
+    pub fn sort_from_nearest(&self, world: &World, coords_vec: &mut [GridPos]) {
+        let known_map = self.known_map(world);
+        let position = self.position();
+        let costs = self.cost_model().costs_from(&known_map, position, true);
+        coords_vec.sort_by_key(|coords| {
+            let cost = costs
+                .get(coords.row)
+                .and_then(|costs| costs.get(coords.col))
+                .copied()
+                .unwrap_or(usize::MAX);
+            // Then by the sum of the absolute differences
+            (cost, coords.manhattan(position))
+        });
     }
 
-    pub(crate) fn valid_lssf_coords(&self, col: usize, row: usize) -> bool {
-        let action_vec = self.lssf.as_ref().unwrap().get_action_vec(col, row);
+    pub(crate) fn valid_lssf_coords(&self, coords: GridPos) -> bool {
+        let (row, col) = coords.to_lssf();
+        let action_vec = self.lssf.as_ref().unwrap().get_action_vec(row, col);
         match action_vec {
             Ok(actions) => !actions.is_empty(),
             Err(_) => false,
@@ -180,12 +308,51 @@ impl Scrapbot {
 
 impl Runnable for Scrapbot {
     fn process_tick(&mut self, world: &mut World) {
-        self.routine(world);
+        self.tick += 1;
+        self.invalidate_known_map();
+        self.blacklist.purge_expired(self.tick);
+        self.metrics.set_position(self.position());
+        let conditions = look_at_sky(world);
+        self.scheduling.observe(&conditions);
+        self.environment = Some(conditions);
+        self.run_auctions(world);
+        self.catalogue_teleports(world);
+
+        // low on energy, let the world recharge us instead of acting
+        if !self.plan_recharge() {
+            self.routine(world);
+        }
 
         self.store_environmental_condition(world);
         self.store_tiles(world);
+
+        let tiles_discovered = self.known_tile_count(world);
+        let energy_level = self.get_energy().get_energy_level();
+        self.recharge.observe_tick_end(energy_level);
+        self.metrics
+            .end_tick(self.tick, tiles_discovered, energy_level);
+        self.publish_metrics();
+        #[cfg(feature = "prometheus")]
+        self.export_metrics();
     }
     fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::TileContentUpdated(_, _) => {
+                // something we scanned is different now
+                self.scan_cache.invalidate();
+                self.invalidate_known_map();
+            }
+            // moving discovers the tiles around us, our own steps patch them
+            Event::Moved(_, _) => self.map_snapshot.borrow_mut().on_moved(),
+            // the others can have our piles
+            Event::Terminated => {
+                if let Some(fleet) = &self.fleet {
+                    fleet.leave();
+                }
+            }
+            _ => {}
+        }
+        self.metrics.record_event(&event);
         self.store_event(event);
     }
     fn get_energy(&self) -> &Energy {
@@ -1,332 +1,569 @@
 use std::collections::VecDeque;
 
-use robotics_lib::interface::{go, robot_map, teleport, Direction};
+use robotics_lib::interface::{go, look_at_sky, teleport, Direction};
 use robotics_lib::runner::Runnable;
 use robotics_lib::utils::LibError;
-use robotics_lib::world::tile::{Tile, TileType};
 use robotics_lib::world::World;
 use sense_and_find_by_rustafariani::Action;
 
-use crate::robot::{BotAction, Scrapbot};
+use crate::robot::{
+    cost_model::elevation_cost,
+    grid::{action_to_direction, direction_to_action, GridPos},
+    map_snapshot::{map_dims, tile_at},
+    navigation::{is_hazard, is_safe_tile, neighbour, plan_safe_path},
+    obstacles::is_obstacle,
+    Scrapbot,
+};
+
+/// How far from the border we can keep on an axis of `len` tiles: `wanted`,
+/// or less on small maps so that at least one tile is left in between.
+fn border_margin(len: usize, wanted: usize) -> usize {
+    wanted.min(len.saturating_sub(1) / 2)
+}
 
-fn is_undiscovered_tile(known_map: &[Vec<Option<Tile>>], x: usize, y: usize) -> bool {
-    known_map[x][y].is_none()
+/// `value` moved at least `min_distance` tiles away from both ends of an
+/// axis of `len` tiles, or as far as the axis allows.
+fn keep_off_border(value: usize, len: usize, min_distance: usize) -> usize {
+    if len == 0 {
+        return value;
+    }
+    let margin = border_margin(len, min_distance);
+    value.clamp(margin, len - 1 - margin)
 }
 
-fn valid_coords_no_border(x: i32, y: i32, map_size: i32) -> bool {
-    x >= 0 && y >= 0 && x < map_size && y < map_size
+/// Brings a coordinate that left the `margin`-wide band along the border of
+/// an axis of `len` tiles back in from the opposite side.
+fn wrap_to_interior(value: i64, len: usize, margin: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    if value < margin as i64 {
+        len - 1 - margin
+    } else if value >= (len - margin) as i64 {
+        margin
+    } else {
+        value as usize
+    }
 }
 
 impl Scrapbot {
-    pub(crate) fn move_away_from_border(&mut self, world: &mut World) -> bool {
-        let map_size = robot_map(world).unwrap().len();
-        let robot_pos = self.get_coordinate();
-        let (robot_x, robot_y) = (robot_pos.get_col(), robot_pos.get_row());
-
-        let min_distance = 4;
-        let mut moved = true;
-
-        let mut moves_stack: Vec<Direction> = vec![];
-
-        match (
-            robot_x < min_distance,
-            robot_y < min_distance,
-            robot_y >= map_size - min_distance,
-            robot_x >= map_size - min_distance,
-        ) {
-            (true, false, false, false) => {
-                moves_stack.extend(std::iter::repeat(Direction::Right).take(min_distance))
-            }
-            (false, true, false, false) => {
-                moves_stack.extend(std::iter::repeat(Direction::Down).take(min_distance))
+    /// Moves one tile, failed moves are counted in the metrics and what the
+    /// move cost is remembered for the cost model.
+    ///
+    /// Steps onto unknown or hazardous tiles (lava, deep water, walls) are
+    /// refused before calling the library, so they don't cost anything.
+    pub(crate) fn step(&mut self, world: &mut World, direction: Direction) -> bool {
+        let known_map = self.known_map(world);
+        let (from, to) = match neighbour(&known_map, self.position(), &direction) {
+            Some(next) if is_safe_tile(&known_map, next) => (
+                tile_at(&known_map, self.position()).cloned(),
+                tile_at(&known_map, next).cloned(),
+            ),
+            _ => {
+                self.metrics.hazards_avoided += 1;
+                return false;
             }
-            (false, false, true, false) => {
-                moves_stack.extend(std::iter::repeat(Direction::Up).take(min_distance))
+        };
+        // trees and rocks on the way are destroyed if the policy allows it
+        if !self.clear_obstacle(world, &known_map, &direction) {
+            self.metrics.failed_moves += 1;
+            return false;
+        }
+        // let go of the snapshot so the move can be patched in place
+        drop(known_map);
+
+        self.map_snapshot.borrow_mut().expect_own_move();
+        let level = self.get_energy().get_energy_level();
+        match go(self, world, direction) {
+            Ok((view, (row, col))) => {
+                self.map_snapshot
+                    .borrow_mut()
+                    .patch(GridPos::new(row, col), &view);
+                if let (Some(from), Some(to)) = (from, to) {
+                    let spent = level.saturating_sub(self.get_energy().get_energy_level());
+                    let climb = elevation_cost(from.elevation, to.elevation);
+                    self.go_costs.observe(
+                        &look_at_sky(world),
+                        &to.tile_type,
+                        spent.saturating_sub(climb),
+                    );
+                }
+                true
             }
-            (false, false, false, true) => {
-                moves_stack.extend(std::iter::repeat(Direction::Left).take(min_distance))
+            Err(_) => {
+                self.map_snapshot.borrow_mut().forget_own_move();
+                self.metrics.failed_moves += 1;
+                false
             }
-            _ => moved = false,
         }
+    }
 
-        for direction in &moves_stack {
-            go(self, world, direction.clone()).ok();
-            // print!("Moved away from border, {:?} | ", direction);
+    /// Walks towards `target` over safe known tiles, stopping on the safe tile
+    /// closest to it. Fails with `CannotWalk` if no safe step gets us closer.
+    pub(crate) fn go_to_safely(
+        &mut self,
+        world: &mut World,
+        target: GridPos,
+    ) -> Result<(), LibError> {
+        let known_map = self.known_map(world);
+        let path = plan_safe_path(
+            &known_map,
+            &self.cost_model(),
+            self.position(),
+            target,
+            false,
+        )
+        .ok_or(LibError::CannotWalk)?;
+        // every step patches the snapshot, which copies it while we hold it
+        drop(known_map);
+
+        for direction in path {
+            if !self.step(world, direction) {
+                return Err(LibError::CannotWalk);
+            }
         }
-
-        self.lssf_update(world, Some(min_distance * 2));
-
-        moved
+        Ok(())
     }
 
-    pub(crate) fn move_to_center(&mut self, world: &mut World) {
-        let map_size = robot_map(world).unwrap().len();
-        let robot_pos = self.get_coordinate();
-        let (robot_x, robot_y) = (robot_pos.get_col(), robot_pos.get_row());
-        let center = map_size / 2;
+    /// Moves at least `min_distance` tiles away from the borders we're on,
+    /// returns whether we had to move. On maps too small for that we get as
+    /// close to the middle as we can.
+    pub(crate) fn move_away_from_border(&mut self, world: &mut World) -> Result<bool, LibError> {
+        let (rows, cols) = self.world_dims(world);
+        let position = self.position();
+        let min_distance = 4;
 
-        let mut moves_stack: Vec<Direction> = vec![];
+        let target = GridPos::new(
+            keep_off_border(position.row, rows, min_distance),
+            keep_off_border(position.col, cols, min_distance),
+        );
 
-        match (robot_x < center, robot_y < center) {
-            (true, true) => {
-                moves_stack.extend(std::iter::repeat(Direction::Right).take(center - robot_x));
-                moves_stack.extend(std::iter::repeat(Direction::Down).take(center - robot_y));
-            }
-            (true, false) => {
-                moves_stack.extend(std::iter::repeat(Direction::Right).take(center - robot_x));
-                moves_stack.extend(std::iter::repeat(Direction::Up).take(robot_y - center));
-            }
-            (false, true) => {
-                moves_stack.extend(std::iter::repeat(Direction::Left).take(robot_x - center));
-                moves_stack.extend(std::iter::repeat(Direction::Down).take(center - robot_y));
-            }
-            (false, false) => {
-                moves_stack.extend(std::iter::repeat(Direction::Left).take(robot_x - center));
-                moves_stack.extend(std::iter::repeat(Direction::Up).take(robot_y - center));
-            }
+        let moved = target != position;
+        if moved {
+            self.go_to_safely(world, target)?;
         }
 
-        for direction in &moves_stack {
-            go(self, world, direction.clone()).ok();
-            // print!("Moved to center, {:?} | ", direction);
-        }
+        self.lssf_update(world, Some(min_distance * 2));
 
-        self.lssf_update(world, Some((center * 2) - 1));
+        Ok(moved)
     }
 
-    pub(crate) fn nearest_border_distance(&self, world: &World) -> usize {
-        let robot_pos = self.get_coordinate();
-        // Assumiamo che robot_map(world) restituisca una griglia quadrata,
-        // quindi prendiamo la lunghezza di uno dei lati per calcolare world_size.
-
-        let world_size = robot_map(world).unwrap().len();
+    pub(crate) fn move_to_center(&mut self, world: &mut World) -> Result<(), LibError> {
+        let (rows, cols) = self.world_dims(world);
+        let center = GridPos::new(rows / 2, cols / 2);
 
-        let row = robot_pos.get_row();
-        let col = robot_pos.get_col();
+        let result = self.go_to_safely(world, center);
 
-        // Calcola le distanze dai quattro bordi della mappa.
-        let dist_top = row; // Distanza dal bordo superiore.
-        let dist_bottom = world_size - row - 1; // Distanza dal bordo inferiore.
-        let dist_left = col; // Distanza dal bordo sinistro.
-        let dist_right = world_size - col - 1; // Distanza dal bordo destro.
+        self.lssf_update(world, Some(rows.min(cols).saturating_sub(1)));
+        result
+    }
 
-        // Restituisce la distanza minima tra quelle calcolate.
-        *[dist_top, dist_bottom, dist_left, dist_right]
-            .iter()
-            .min()
-            .unwrap()
+    /// Tiles between us and the closest border, 0 when we're on one.
+    pub(crate) fn nearest_border_distance(&self, world: &World) -> usize {
+        let (rows, cols) = self.world_dims(world);
+        let position = self.position();
+
+        [
+            position.row,
+            rows.saturating_sub(position.row + 1),
+            position.col,
+            cols.saturating_sub(position.col + 1),
+        ]
+        .into_iter()
+        .min()
+        .unwrap()
     }
 
-    fn opposite_border_coords(x: i32, y: i32, map_size: i32) -> (i32, i32) {
-        let new_x = if x < 4 {
-            map_size - 5
-        } else if x >= map_size - 4 {
-            4
-        } else {
-            x
-        };
-        let new_y = if y < 4 {
-            map_size - 5
-        } else if y >= map_size - 4 {
-            4
-        } else {
-            y
+    pub fn find_closest_undiscovered_tile(&mut self, world: &mut World) -> Option<GridPos> {
+        let position = self.position();
+        let known_map = self.known_map(world);
+        let (rows, cols) = map_dims(&known_map);
+        if !position.is_within(rows, cols) {
+            return None;
+        }
+
+        // Distance from the border to be avoided, as much as the map allows
+        let border_limit = 4;
+        let row_margin = border_margin(rows, border_limit);
+        let col_margin = border_margin(cols, border_limit);
+        let inside = |pos: GridPos| {
+            (row_margin..rows - row_margin).contains(&pos.row)
+                && (col_margin..cols - col_margin).contains(&pos.col)
         };
-        (new_x, new_y)
-    }
 
-    pub fn find_closest_undiscovered_tile(&mut self, world: &mut World) -> Option<(usize, usize)> {
-        let robot_x = self.get_coordinate().get_row();
-        let robot_y = self.get_coordinate().get_col();
-        let known_map = robot_map(world).unwrap();
-        let map_size = known_map.len() as i32;
-        let border_limit = 4; // Distance from the border to be avoided
-        let mut visited = vec![vec![false; map_size as usize]; map_size as usize];
+        let mut visited = vec![vec![false; cols]; rows];
         let mut queue = VecDeque::new();
+        // with a fleet, only look for tiles in our own sector
+        let sector = self.exploration_sector(rows, cols);
+        // only tiles we could stand on, a plan never ends on a hazard
+        let is_candidate = |pos: GridPos| {
+            is_safe_tile(&known_map, pos)
+                && sector.contains(pos)
+                && !self.blacklist.is_banned(pos, self.tick)
+                && self.valid_lssf_coords(pos)
+        };
 
         // Mark the border and impassable tiles as visited
-        for i in 0..map_size {
-            for j in 0..map_size {
-                if i < border_limit
-                    || i >= map_size - border_limit
-                    || j < border_limit
-                    || j >= map_size - border_limit
-                {
-                    visited[i as usize][j as usize] = true;
-                } else if let Some(tile) = &known_map[i as usize][j as usize] {
-                    match tile.tile_type {
-                        TileType::Lava | TileType::DeepWater | TileType::Wall => {
-                            visited[i as usize][j as usize] = true
-                        }
-                        _ => {}
+        for (row, tiles) in known_map.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                if !inside(GridPos::new(row, col)) {
+                    visited[row][col] = true;
+                } else if let Some(tile) = tile {
+                    if is_hazard(&tile.tile_type) {
+                        visited[row][col] = true;
                     }
                 }
             }
         }
 
         // Start BFS from the robot's position
-        queue.push_back((robot_x, robot_y));
-        visited[robot_x][robot_y] = true;
-
-        while let Some((x, y)) = queue.pop_front() {
-            let x_i = x as i32;
-            let y_i = y as i32;
-
-            for (dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
-                let (nx, ny) = (x_i + dx, y_i + dy);
-                if Self::valid_coords(nx, ny, map_size, border_limit) {
-                    let (nx_usize, ny_usize) = (nx as usize, ny as usize);
-                    if !visited[nx_usize][ny_usize] {
-                        if known_map[nx_usize][ny_usize].is_none() {
-                            // Found an undiscovered tile, now return any adjacent discovered tile
-                            for (adx, ady) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
-                                let (ax, ay) = (nx + adx, ny + ady);
-                                if Self::valid_coords(ax, ay, map_size, border_limit) {
-                                    let (ax_usize, ay_usize) = (ax as usize, ay as usize);
-                                    if known_map[ax_usize][ay_usize].is_some()
-                                        && self.valid_lssf_coords(ax_usize, ay_usize)
-                                    {
-                                        return Some((ax_usize, ay_usize));
-                                    }
-                                }
-                            }
-                        }
-                        queue.push_back((nx_usize, ny_usize));
-                        visited[nx_usize][ny_usize] = true;
+        queue.push_back(position);
+        visited[position.row][position.col] = true;
+
+        while let Some(current) = queue.pop_front() {
+            for (dr, dc) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let (nr, nc) = (current.row as i64 + dr, current.col as i64 + dc);
+                // past the border margin we carry on from the opposite side
+                let next = GridPos::new(
+                    wrap_to_interior(nr, rows, row_margin),
+                    wrap_to_interior(nc, cols, col_margin),
+                );
+                if visited[next.row][next.col] {
+                    continue;
+                }
+
+                let wrapped = (next.row as i64, next.col as i64) != (nr, nc);
+                if wrapped {
+                    if is_candidate(next) {
+                        return Some(next);
                     }
-                } else {
-                    // Handle the case where the robot is at the border
-                    let (opposite_x, opposite_y) = Self::opposite_border_coords(nx, ny, map_size);
-                    if Self::valid_coords(opposite_x, opposite_y, map_size, border_limit) {
-                        let (opposite_usize_x, opposite_usize_y) =
-                            (opposite_x as usize, opposite_y as usize);
-                        if !visited[opposite_usize_x][opposite_usize_y] {
-                            if known_map[opposite_usize_x][opposite_usize_y].is_some()
-                                && self.valid_lssf_coords(opposite_usize_x, opposite_usize_y)
-                            {
-                                return Some((opposite_usize_x, opposite_usize_y));
-                            }
-                            queue.push_back((opposite_usize_x, opposite_usize_y));
-                            visited[opposite_usize_x][opposite_usize_y] = true;
-                        }
+                } else if known_map[next.row][next.col].is_none() {
+                    // Found an undiscovered tile, now return any adjacent discovered tile
+                    if let Some((_, adjacent)) = next
+                        .neighbours(rows, cols)
+                        .find(|(_, adjacent)| inside(*adjacent) && is_candidate(*adjacent))
+                    {
+                        return Some(adjacent);
                     }
                 }
+                queue.push_back(next);
+                visited[next.row][next.col] = true;
             }
         }
 
         // If no undiscovered tile is found, return a coordinate near the center
-        Some((map_size as usize / 2, map_size as usize / 2))
-    }
-
-    fn valid_coords(x: i32, y: i32, map_size: i32, border_limit: i32) -> bool {
-        x >= border_limit
-            && x < map_size - border_limit
-            && y >= border_limit
-            && y < map_size - border_limit
+        // of our sector (the whole map when we're alone)
+        Some(sector.center())
     }
 
-    pub fn populate_action_vec_given_point(&mut self, world: &World, coordinate: (usize, usize)) {
+    /// Plans the path to `coordinate`, returns false if LSSF can't reach it.
+    pub fn populate_action_vec_given_point(&mut self, world: &World, coordinate: GridPos) -> bool {
         let old_lssf = self.lssf.take().unwrap();
-        match old_lssf.get_action_vec(coordinate.0, coordinate.1) {
-            // col(x), row(y)
+        let (row, col) = coordinate.to_lssf();
+        let planned = match old_lssf.get_action_vec(row, col) {
             Ok(actions) => {
                 // println!("Populated action vec!: {:?}", actions);
                 self.actions_vec = Some(actions);
+                true
             }
             Err(_) => {
                 // println!("Error planning next move to: {:?} | {:?}", coordinate, err);
+                // don't leave the previous plan around, it would be run again
+                self.actions_vec = Some(vec![]);
+                false
             }
-        }
+        };
         self.lssf = Some(old_lssf);
+        planned
     }
-    pub(crate) fn run_action_vec_and_then(
+    /// Runs a plan: hazards are walked around, long trips may go through a
+    /// teleport and nothing is done if we can't afford it. The plan actually
+    /// run is left in `actions_vec`. `delivering` trips are counted for road
+    /// building.
+    pub(crate) fn run_plan(
         &mut self,
         world: &mut World,
-        action: BotAction,
-    ) -> Result<usize, LibError> {
-        // Run the actions vector if it exists
-        if let Some(mut actions) = self.actions_vec.take() {
-            // check if the action vector is empty
-            if actions.is_empty() {
-                // println!("No actions to perform");
-                return Err(LibError::CannotWalk);
-            }
-
-            let mut last_move_direction = None;
-
-            // Get the last move direction from the first element of the actions vector
-            match action {
-                BotAction::Destroy | BotAction::Put => {
-                    last_move_direction = match actions.remove(0) {
-                        Action::North => Some(Direction::Up),
-                        Action::South => Some(Direction::Down),
-                        Action::East => Some(Direction::Right),
-                        Action::West => Some(Direction::Left),
-                        _ => None, // Default to Up, though ideally should never hit this case
-                    };
+        mut actions: Vec<Action>,
+        delivering: bool,
+    ) -> Result<(), LibError> {
+        // LSSF doesn't know about hazards, go around them or give up. It
+        // doesn't know about obstacles either: the planner only keeps the
+        // destroy if it's cheaper than walking around.
+        if !self.actions_are_safe(world, &actions) || self.actions_cross_obstacle(world, &actions) {
+            let destination = self.actions_destination(&actions);
+            let known_map = self.known_map(world);
+            let model = self.cost_model();
+            // a tree or rock we came for is collected from next to it
+            let exact =
+                tile_at(&known_map, destination).is_none_or(|tile| !is_obstacle(&tile.content));
+            match plan_safe_path(&known_map, &model, self.position(), destination, exact) {
+                Some(path) => actions = path.iter().map(direction_to_action).collect(),
+                None => {
+                    self.actions_vec = Some(vec![]);
+                    return Err(LibError::CannotWalk);
                 }
-                _ => (),
             }
+        }
 
-            // Execute the actions in the vector
-            self.full_recharge();
-            for action in &actions {
-                match action {
-                    Action::North => {
-                        go(self, world, Direction::Up).ok();
-                    }
-                    Action::South => {
-                        go(self, world, Direction::Down).ok();
-                    }
-                    Action::East => {
-                        go(self, world, Direction::Right).ok();
-                    }
-                    Action::West => {
-                        go(self, world, Direction::Left).ok();
-                    }
-                    Action::Teleport(row, col) => {
-                        teleport(self, world, (*row, *col)).ok();
+        // long trips may be cheaper through a teleport
+        self.use_teleport_shortcut(world, &mut actions);
+
+        // Execute the actions in the vector
+        let energy_needed = self.plan_energy(world, &actions).unwrap_or(0);
+        if self.get_energy().get_energy_level() < energy_needed {
+            self.actions_vec = Some(actions);
+            return Err(LibError::NotEnoughEnergy);
+        }
+        for action in &actions {
+            let direction = match action_to_direction(action) {
+                Some(direction) => direction,
+                // only teleports have no direction
+                None => {
+                    let pad = self.position().step_action(action);
+                    if pad.is_none_or(|pad| teleport(self, world, pad.into()).is_err()) {
+                        self.metrics.failed_moves += 1;
+                        self.actions_vec = Some(vec![]);
+                        return Err(LibError::CannotWalk);
                     }
+                    continue;
                 }
+            };
+            // the rest of the plan starts from a tile we're not on, stop here
+            if !self.step(world, direction) {
+                self.actions_vec = Some(vec![]);
+                return Err(LibError::CannotWalk);
+            }
+            // remember the tiles crossed on delivery trips
+            if delivering {
+                self.roads.record_crossing(self.position());
             }
+        }
 
-            self.actions_vec = Some(actions); // Put the modified vector back
+        self.actions_vec = Some(actions); // Put the modified vector back
+        Ok(())
+    }
 
-            // Perform the final action
-            self.full_recharge();
-            let result = match action {
-                BotAction::Destroy => {
-                    self.collect_trash_in_front_of(world, last_move_direction.unwrap())
-                }
-                BotAction::Put => {
-                    self.drop_trash_into_bin_in_front_of(world, last_move_direction.unwrap())
+    /// Runs the plan in `actions_vec`, fails with `CannotWalk` if it's empty.
+    pub(crate) fn run_action_vec(&mut self, world: &mut World) -> Result<(), LibError> {
+        match self.actions_vec.take() {
+            Some(actions) if actions.is_empty() => Err(LibError::CannotWalk),
+            Some(actions) => self.run_plan(world, actions, false),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::world::tile::{Content, TileType};
+
+    use super::*;
+    use crate::robot::{
+        blacklist::BlacklistReason,
+        test_world::{test_bot, TestWorld},
+    };
+
+    fn east(steps: usize) -> Vec<Action> {
+        (0..steps)
+            .map(|_| direction_to_action(&Direction::Right))
+            .collect()
+    }
+
+    #[test]
+    fn border_margin_shrinks_with_short_axes() {
+        assert_eq!(border_margin(0, 4), 0);
+        assert_eq!(border_margin(1, 4), 0);
+        assert_eq!(border_margin(2, 4), 0);
+        assert_eq!(border_margin(3, 4), 1);
+        assert_eq!(border_margin(20, 4), 4);
+        assert_eq!(border_margin(5, 4), 2);
+    }
+
+    #[test]
+    fn keep_off_border_stays_on_the_axis() {
+        assert_eq!(keep_off_border(7, 0, 4), 7);
+        assert_eq!(keep_off_border(5, 1, 4), 0);
+        assert_eq!(keep_off_border(5, 2, 4), 1);
+        assert_eq!(keep_off_border(0, 3, 4), 1);
+        assert_eq!(keep_off_border(2, 3, 4), 1);
+        // 20x5 map, each axis keeps its own margin
+        assert_eq!(keep_off_border(0, 20, 4), 4);
+        assert_eq!(keep_off_border(19, 20, 4), 15);
+        assert_eq!(keep_off_border(10, 20, 4), 10);
+        assert_eq!(keep_off_border(0, 5, 4), 2);
+        assert_eq!(keep_off_border(4, 5, 4), 2);
+    }
+
+    #[test]
+    fn wrap_to_interior_lands_inside_the_margin() {
+        assert_eq!(wrap_to_interior(-1, 0, 0), 0);
+        assert_eq!(wrap_to_interior(-1, 1, 0), 0);
+        assert_eq!(wrap_to_interior(1, 1, 0), 0);
+        assert_eq!(wrap_to_interior(-1, 2, 0), 1);
+        assert_eq!(wrap_to_interior(2, 2, 0), 0);
+        assert_eq!(wrap_to_interior(1, 2, 0), 1);
+        assert_eq!(wrap_to_interior(0, 3, 1), 1);
+        assert_eq!(wrap_to_interior(2, 3, 1), 1);
+        // 20x5 map with the margins of border_margin
+        assert_eq!(wrap_to_interior(3, 20, 4), 15);
+        assert_eq!(wrap_to_interior(16, 20, 4), 4);
+        assert_eq!(wrap_to_interior(10, 20, 4), 10);
+        assert_eq!(wrap_to_interior(1, 5, 2), 2);
+        assert_eq!(wrap_to_interior(3, 5, 2), 2);
+    }
+
+    // 9x9 grass, we start in the middle with lava right ahead
+    fn lava_ahead(lava_rows: std::ops::RangeInclusive<usize>) -> TestWorld {
+        lava_rows.fold(
+            TestWorld::grass(9, 9).spawn_at(GridPos::new(4, 4)),
+            |world, row| world.with_tile_type(GridPos::new(row, 5), TileType::Lava),
+        )
+    }
+
+    #[test]
+    fn lava_on_the_straight_line_is_walked_around() {
+        lava_ahead(3..=5).run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            let start = bot.position();
+            let target = GridPos::new(4, 7);
+
+            // what the detour should cost, from the same known map
+            let detour: Vec<Action> = plan_safe_path(
+                &bot.known_map(world),
+                &bot.cost_model(),
+                start,
+                target,
+                true,
+            )
+            .unwrap()
+            .iter()
+            .map(direction_to_action)
+            .collect();
+            let expected = bot.plan_energy(world, &detour).unwrap();
+
+            let energy_before = bot.metrics.energy_consumed;
+            bot.run_plan(world, east(3), false).unwrap();
+
+            assert_eq!(bot.position(), target);
+            assert_eq!(bot.metrics.hazards_avoided, 0);
+            assert_eq!(bot.metrics.failed_moves, 0);
+            assert_eq!(bot.metrics.energy_consumed - energy_before, expected);
+        });
+    }
+
+    #[test]
+    fn lava_wall_stops_the_plan_before_moving() {
+        lava_ahead(0..=8).run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            let start = bot.position();
+            let energy_before = bot.metrics.energy_consumed;
+
+            assert!(matches!(
+                bot.run_plan(world, east(3), false),
+                Err(LibError::CannotWalk)
+            ));
+            assert_eq!(bot.position(), start);
+            assert_eq!(bot.metrics.energy_consumed, energy_before);
+        });
+    }
+
+    #[test]
+    fn trees_are_walked_around_unless_clearing_is_on() {
+        let world = || {
+            TestWorld::grass(9, 9)
+                .spawn_at(GridPos::new(4, 4))
+                .with_content(GridPos::new(4, 5), Content::Tree(2))
+        };
+        world().run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            bot.run_plan(world, east(3), false).unwrap();
+            assert_eq!(bot.position(), GridPos::new(4, 7));
+            assert_eq!(bot.metrics.obstacles_cleared, 0);
+        });
+        // going through costs less than the two extra steps around it
+        let mut bot = test_bot().with_obstacle_clearing();
+        bot.obstacles.destroy_cost = 0;
+        bot.obstacles.slot_cost = 0;
+        world().run(bot, |bot, world| {
+            bot.lssf_update(world, Some(7));
+            bot.run_plan(world, east(3), false).unwrap();
+            assert_eq!(bot.position(), GridPos::new(4, 7));
+            assert_eq!(bot.metrics.obstacles_cleared, 1);
+        });
+    }
+
+    #[test]
+    fn walks_patch_the_snapshot_instead_of_copying_it() {
+        TestWorld::grass(9, 9)
+            .spawn_at(GridPos::new(4, 4))
+            .run(test_bot(), |bot, world| {
+                bot.lssf_update(world, Some(7));
+                bot.known_map(world);
+                let refreshes = bot.map_snapshot.borrow().get_refreshes();
+
+                bot.go_to_safely(world, GridPos::new(4, 7)).unwrap();
+                bot.approach_adjacent(world, GridPos::new(1, 4), false)
+                    .unwrap();
+                bot.known_map(world);
+
+                assert_eq!(bot.position(), GridPos::new(2, 4));
+                assert_eq!(bot.map_snapshot.borrow().get_refreshes(), refreshes);
+            });
+    }
+
+    #[test]
+    fn exploring_tiny_and_narrow_maps_stays_on_the_map() {
+        for (rows, cols) in [(1, 1), (2, 3), (20, 5)] {
+            TestWorld::grass(rows, cols).run(test_bot(), move |bot, world| {
+                bot.move_away_from_border(world).ok();
+                assert!(bot.position().is_within(rows, cols));
+                if let Some(tile) = bot.find_closest_undiscovered_tile(world) {
+                    assert!(tile.is_within(rows, cols));
                 }
-                BotAction::Start => {
-                    self.actions_vec.as_mut().unwrap().clear();
-                    return Ok(0);
+                bot.lssf_update(world, None);
+                bot.spyglass_explore(world);
+                assert!(bot.position().is_within(rows, cols));
+            });
+        }
+    }
+
+    #[test]
+    fn lava_on_the_frontier_is_never_a_target() {
+        // the tiles we know around us are banned but for a corner of lava,
+        // the unknown tile below it is the closest one left
+        let lava = GridPos::new(11, 11);
+        TestWorld::grass(20, 20)
+            .spawn_at(GridPos::new(10, 10))
+            .with_tile_type(lava, TileType::Lava)
+            .run(test_bot(), move |bot, world| {
+                bot.lssf_update(world, Some(3));
+                for row in 9..=11 {
+                    for col in 9..=11 {
+                        let pos = GridPos::new(row, col);
+                        if pos != lava && pos != bot.position() {
+                            bot.blacklist
+                                .ban(pos, BlacklistReason::Unreachable, bot.tick);
+                        }
+                    }
                 }
-                BotAction::Walk => return Ok(0),
-            };
 
-            match result {
-                Ok(q) => Ok(q),
-                Err(err) => Err(err),
-            }
-        } else {
-            Ok(0)
-        }
+                let target = bot.find_closest_undiscovered_tile(world).unwrap();
+                assert_ne!(target, lava);
+                assert!(is_safe_tile(&bot.known_map(world), target));
+            });
     }
 
-    pub(crate) fn get_last_move_direction(&self) -> Option<Direction> {
-        if self.actions_vec.as_ref().unwrap().is_empty() {
-            return None;
-        }
-        match self.actions_vec.as_ref().unwrap().last().unwrap() {
-            Action::North => Some(Direction::Up),
-            Action::South => Some(Direction::Down),
-            Action::East => Some(Direction::Right),
-            Action::West => Some(Direction::Left),
-            _ => None, // hope it doesnt get here
-        }
+    #[test]
+    fn step_into_lava_is_refused() {
+        lava_ahead(4..=4).run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            let start = bot.position();
+            let energy_before = bot.metrics.energy_consumed;
+
+            assert!(!bot.step(world, Direction::Right));
+            assert_eq!(bot.position(), start);
+            assert_eq!(bot.metrics.hazards_avoided, 1);
+            assert_eq!(bot.metrics.energy_consumed, energy_before);
+        });
     }
 }
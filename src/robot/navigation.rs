@@ -0,0 +1,159 @@
+use robotics_lib::interface::Direction;
+use robotics_lib::runner::Runnable;
+use robotics_lib::utils::LibError;
+use robotics_lib::world::tile::TileType;
+use robotics_lib::world::World;
+use sense_and_find_by_rustafariani::Action;
+
+use crate::robot::{
+    cost_model::{walk_back, CostModel},
+    grid::{action_to_direction, direction_to_action, GridPos},
+    map_snapshot::{map_dims, tile_at, KnownMap},
+    obstacles::is_obstacle,
+    Scrapbot,
+};
+
+/// Tiles we never step on: they either kill the robot or just burn energy.
+pub fn is_hazard(tile_type: &TileType) -> bool {
+    matches!(
+        tile_type,
+        TileType::Lava | TileType::DeepWater | TileType::Wall
+    ) || !tile_type.properties().walk()
+}
+
+/// A tile is safe only if we know it and it's not a hazard, we never walk
+/// into the unknown.
+pub fn is_safe_tile(known_map: &KnownMap, pos: GridPos) -> bool {
+    tile_at(known_map, pos).is_some_and(|tile| !is_hazard(&tile.tile_type))
+}
+
+/// Tile reached moving from `pos` towards `direction`, if it's inside the
+/// map.
+pub fn neighbour(known_map: &KnownMap, pos: GridPos, direction: &Direction) -> Option<GridPos> {
+    let (rows, cols) = map_dims(known_map);
+    pos.step(direction)
+        .filter(|next| next.is_within(rows, cols))
+}
+
+/// Cheapest path (according to `model`) over safe known tiles from `from` to
+/// the tile closest to `to`. With `exact` the path must end on `to`.
+///
+/// Returns `None` when no safe step brings us closer.
+pub fn plan_safe_path(
+    known_map: &KnownMap,
+    model: &CostModel,
+    from: GridPos,
+    to: GridPos,
+    exact: bool,
+) -> Option<Vec<Direction>> {
+    let (costs, parents) = model.dijkstra(known_map, from, false);
+
+    // closest reachable tile, the cheapest one among equally close ones
+    let best = costs
+        .iter()
+        .enumerate()
+        .flat_map(|(row, tiles)| {
+            tiles
+                .iter()
+                .enumerate()
+                .filter(|(_, cost)| **cost != usize::MAX)
+                .map(move |(col, cost)| (GridPos::new(row, col), *cost))
+        })
+        .min_by_key(|(pos, cost)| (pos.manhattan(to), *cost))
+        .map(|(pos, _)| pos)?;
+
+    if (exact && best != to) || (best == from && from != to) {
+        return None;
+    }
+    Some(walk_back(&parents, best))
+}
+
+impl Scrapbot {
+    pub(crate) fn position(&self) -> GridPos {
+        GridPos::from(self.get_coordinate())
+    }
+
+    /// Checks every step of an LSSF plan against the known map, obstacles
+    /// included.
+    pub(crate) fn actions_are_safe(&self, world: &World, actions: &[Action]) -> bool {
+        let known_map = self.known_map(world);
+        let model = self.cost_model();
+        let mut position = self.position();
+        for action in actions {
+            let direction = match action_to_direction(action) {
+                Some(direction) => direction,
+                None => {
+                    position = position.step_action(action).unwrap_or(position);
+                    continue;
+                }
+            };
+            match neighbour(&known_map, position, &direction) {
+                Some(next)
+                    if is_safe_tile(&known_map, next)
+                        && model.step(&known_map, position, next).is_some() =>
+                {
+                    position = next
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// True if an LSSF plan walks onto a known tree or rock.
+    pub(crate) fn actions_cross_obstacle(&self, world: &World, actions: &[Action]) -> bool {
+        let known_map = self.known_map(world);
+        let mut position = self.position();
+        actions.iter().any(|action| {
+            position = position.step_action(action).unwrap_or(position);
+            tile_at(&known_map, position).is_some_and(|tile| is_obstacle(&tile.content))
+        })
+    }
+
+    /// Walks to the cheapest free tile next to `target` and returns the
+    /// direction to face it, for `destroy` and `put`.
+    ///
+    /// The direction comes from where we actually end up, not from the last
+    /// step of the plan: teleports and detours don't have a meaningful one.
+    /// `delivering` trips are counted for road building.
+    pub(crate) fn approach_adjacent(
+        &mut self,
+        world: &mut World,
+        target: GridPos,
+        delivering: bool,
+    ) -> Result<Direction, LibError> {
+        if let Some(direction) = self.position().direction_to(target) {
+            return Ok(direction);
+        }
+
+        let known_map = self.known_map(world);
+        let (rows, cols) = map_dims(&known_map);
+        let (costs, parents) = self
+            .cost_model()
+            .dijkstra(&known_map, self.position(), false);
+        let stand = target
+            .neighbours(rows, cols)
+            .map(|(_, tile)| tile)
+            .filter(|tile| costs[tile.row][tile.col] != usize::MAX)
+            .min_by_key(|tile| costs[tile.row][tile.col])
+            .ok_or(LibError::CannotWalk)?;
+
+        let plan = walk_back(&parents, stand)
+            .iter()
+            .map(direction_to_action)
+            .collect();
+        drop(known_map);
+        self.run_plan(world, plan, delivering)?;
+
+        self.position()
+            .direction_to(target)
+            .ok_or(LibError::CannotWalk)
+    }
+
+    /// Where an LSSF plan ends, teleports included.
+    pub(crate) fn actions_destination(&self, actions: &[Action]) -> GridPos {
+        actions.iter().fold(self.position(), |position, action| {
+            position.step_action(action).unwrap_or(position)
+        })
+    }
+}
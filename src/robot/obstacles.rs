@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::mem::{discriminant, Discriminant};
+
+use robot_for_visualizer::RobotForVisualizer;
+use robotics_lib::interface::{destroy, Direction};
+use robotics_lib::world::tile::{Content, Tile};
+use robotics_lib::world::World;
+
+use crate::robot::{
+    collection::content_quantity,
+    map_snapshot::{tile_at, KnownMap},
+    navigation::neighbour,
+    Scrapbot,
+};
+
+/// Contents that stop us from walking on a tile until they're destroyed.
+pub fn is_obstacle(content: &Content) -> bool {
+    matches!(content, Content::Tree(_) | Content::Rock(_))
+}
+
+/// Extra energy the planner charges to go through an obstacle instead of
+/// around it.
+#[derive(Debug, Clone, Copy)]
+pub struct ClearingCost {
+    /// Energy spent by `destroy`.
+    pub destroy_cost: usize,
+    /// What a backpack slot filled by the materials is worth to us.
+    pub slot_cost: usize,
+    /// Wood we can take, bigger trees can't be cleared.
+    pub takeable_wood: usize,
+    /// Rocks we can take, bigger rocks can't be cleared.
+    pub takeable_rocks: usize,
+}
+
+impl ClearingCost {
+    /// Cost of clearing `tile`, `None` if we can't.
+    pub fn for_tile(&self, tile: &Tile) -> Option<usize> {
+        let takeable = match tile.content {
+            Content::Tree(_) => self.takeable_wood,
+            Content::Rock(_) => self.takeable_rocks,
+            _ => 0,
+        };
+        let quantity = content_quantity(&tile.content).max(1);
+        (quantity <= takeable).then_some(self.destroy_cost + self.slot_cost * quantity)
+    }
+}
+
+/// Optional obstacle clearing: trees and rocks on the way may be destroyed
+/// when that's cheaper than walking around them. Keeps track of what we got
+/// from them.
+#[derive(Debug, Clone)]
+pub struct ObstaclePolicy {
+    pub enabled: bool,
+    pub destroy_cost: usize,
+    pub slot_cost: usize,
+    materials: HashMap<Discriminant<Content>, usize>,
+}
+
+impl Default for ObstaclePolicy {
+    fn default() -> Self {
+        ObstaclePolicy {
+            enabled: false,
+            destroy_cost: 5,
+            slot_cost: 2,
+            materials: HashMap::new(),
+        }
+    }
+}
+
+impl ObstaclePolicy {
+    pub fn enabled() -> ObstaclePolicy {
+        ObstaclePolicy {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// Clearing costs to give to the planner from what the backpack can
+    /// take of each material, `None` if we won't clear anything.
+    pub fn clearing_cost(
+        &self,
+        takeable_wood: usize,
+        takeable_rocks: usize,
+    ) -> Option<ClearingCost> {
+        (self.enabled && takeable_wood + takeable_rocks > 0).then_some(ClearingCost {
+            destroy_cost: self.destroy_cost,
+            slot_cost: self.slot_cost,
+            takeable_wood,
+            takeable_rocks,
+        })
+    }
+
+    pub fn record_materials(&mut self, content: &Content, quantity: usize) {
+        *self.materials.entry(discriminant(content)).or_insert(0) += quantity;
+    }
+
+    /// Items of the same kind as `content` gained by clearing the way.
+    pub fn get_materials(&self, content: &Content) -> usize {
+        self.materials
+            .get(&discriminant(content))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl Scrapbot {
+    /// Turns obstacle clearing on, the planner weighs destroying trees and
+    /// rocks against the detour.
+    pub fn with_obstacle_clearing(mut self) -> Scrapbot {
+        self.obstacles = ObstaclePolicy::enabled();
+        self
+    }
+
+    /// Destroys the obstacle in `direction` if there is one, returns false if
+    /// it's still there. Obstacles whose materials don't fit in the free,
+    /// unreserved space are left alone.
+    pub(crate) fn clear_obstacle(
+        &mut self,
+        world: &mut World,
+        known_map: &KnownMap,
+        direction: &Direction,
+    ) -> bool {
+        let tile = match neighbour(known_map, self.position(), direction)
+            .and_then(|next| tile_at(known_map, next))
+        {
+            Some(tile) if is_obstacle(&tile.content) => tile,
+            _ => return true,
+        };
+        let clearing = self.obstacles.clearing_cost(
+            self.takeable_quantity(&Content::Tree(0)),
+            self.takeable_quantity(&Content::Rock(0)),
+        );
+        if clearing
+            .and_then(|clearing| clearing.for_tile(tile))
+            .is_none()
+        {
+            return false;
+        }
+        let content = tile.content.clone();
+
+        match destroy(self, world, direction.clone()) {
+            Ok(quantity) => {
+                self.obstacles.record_materials(&content, quantity);
+                self.metrics.obstacles_cleared += 1;
+                self.store_tiles(world);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::utils::LibError;
+    use robotics_lib::world::tile::TileType;
+    use sense_and_find_by_rustafariani::Action;
+
+    use super::*;
+    use crate::robot::{
+        grid::{direction_to_action, GridPos},
+        test_world::{test_bot, TestWorld},
+    };
+
+    const SPAWN: GridPos = GridPos { row: 4, col: 4 };
+    const TARGET: GridPos = GridPos { row: 4, col: 6 };
+
+    fn east(steps: usize) -> Vec<Action> {
+        (0..steps)
+            .map(|_| direction_to_action(&Direction::Right))
+            .collect()
+    }
+
+    // 9x9 grass cut in two by a column of rocks, no way around them
+    fn rock_wall(rocks: usize) -> TestWorld {
+        (0..9).fold(TestWorld::grass(9, 9).spawn_at(SPAWN), |world, row| {
+            world.with_content(GridPos::new(row, 5), Content::Rock(rocks))
+        })
+    }
+
+    fn tile(content: Content) -> Tile {
+        Tile {
+            tile_type: TileType::Grass,
+            content,
+            elevation: 0,
+        }
+    }
+
+    #[test]
+    fn clearing_cost_grows_with_the_materials_and_stops_at_what_fits() {
+        let cost = ObstaclePolicy::enabled().clearing_cost(3, 1).unwrap();
+        assert_eq!(cost.for_tile(&tile(Content::Tree(2))), Some(5 + 2 * 2));
+        assert_eq!(cost.for_tile(&tile(Content::Tree(4))), None);
+        assert_eq!(cost.for_tile(&tile(Content::Rock(1))), Some(5 + 2));
+        assert_eq!(cost.for_tile(&tile(Content::Rock(2))), None);
+
+        assert!(ObstaclePolicy::enabled().clearing_cost(0, 0).is_none());
+        assert!(ObstaclePolicy::default().clearing_cost(3, 3).is_none());
+    }
+
+    #[test]
+    fn enabled_clearing_walks_through_the_wall() {
+        rock_wall(1).run(test_bot().with_obstacle_clearing(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            bot.run_plan(world, east(2), false).unwrap();
+
+            assert_eq!(bot.position(), TARGET);
+            assert_eq!(bot.metrics.obstacles_cleared, 1);
+            assert_eq!(bot.obstacles.get_materials(&Content::Rock(0)), 1);
+            assert_eq!(bot.carried_quantity(&Content::Rock(0)), 1);
+        });
+    }
+
+    #[test]
+    fn disabled_clearing_leaves_the_wall_alone() {
+        rock_wall(1).run(test_bot(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            assert!(matches!(
+                bot.run_plan(world, east(2), false),
+                Err(LibError::CannotWalk)
+            ));
+            assert_eq!(bot.position(), SPAWN);
+            assert_eq!(bot.metrics.obstacles_cleared, 0);
+        });
+    }
+
+    #[test]
+    fn reserved_space_is_not_filled_with_rocks() {
+        rock_wall(3).run(test_bot().with_obstacle_clearing(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            // room for two rocks only, the wall is three rocks thick
+            let capacity = bot.backpack_capacity();
+            bot.backpack_manager
+                .reserve(&Content::Garbage(0), capacity - 2);
+            assert!(bot.cost_model().clearing.is_some());
+
+            assert!(bot.run_plan(world, east(2), false).is_err());
+            assert_eq!(bot.position(), SPAWN);
+            assert_eq!(bot.metrics.obstacles_cleared, 0);
+        });
+    }
+
+    #[test]
+    fn full_backpack_never_clears() {
+        rock_wall(1).run(test_bot().with_obstacle_clearing(), |bot, world| {
+            bot.lssf_update(world, Some(7));
+            let capacity = bot.backpack_capacity();
+            bot.backpack_manager.reserve(&Content::Garbage(0), capacity);
+            assert!(bot.cost_model().clearing.is_none());
+
+            let known_map = bot.known_map(world);
+            assert!(!bot.clear_obstacle(world, &known_map, &Direction::Right));
+            assert_eq!(bot.metrics.obstacles_cleared, 0);
+            assert_eq!(bot.carried_quantity(&Content::Rock(0)), 0);
+        });
+    }
+}
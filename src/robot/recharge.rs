@@ -0,0 +1,162 @@
+use robotics_lib::runner::Runnable;
+
+use crate::robot::Scrapbot;
+
+/// Decides when to stop and let the energy regenerate.
+///
+/// The world gives some energy back every tick, we don't know how much in
+/// advance so `regen_per_tick` starts from a guess and follows what we see
+/// between ticks. A rest lasts as many ticks as the estimate says it takes to
+/// reach its target: `resume_at` when we ran low, the cost of the plan plus
+/// the reserve when a tour was aborted. It ends early if the target is reached
+/// sooner.
+#[derive(Debug, Clone)]
+pub struct RechargePlanner {
+    /// Start resting below this level.
+    pub rest_below: usize,
+    /// Stop resting at this level.
+    pub resume_at: usize,
+    /// Estimated energy regenerated per tick.
+    pub regen_per_tick: f32,
+    /// Most energy the battery holds, no rest goes beyond it.
+    pub capacity: usize,
+    resting: bool,
+    target: usize,
+    ticks_left: usize,
+    last_level: Option<usize>,
+}
+
+impl Default for RechargePlanner {
+    fn default() -> Self {
+        RechargePlanner {
+            rest_below: 100,
+            resume_at: 800,
+            regen_per_tick: 10.0,
+            capacity: 1000,
+            resting: false,
+            target: 0,
+            ticks_left: 0,
+            last_level: None,
+        }
+    }
+}
+
+impl RechargePlanner {
+    pub fn is_resting(&self) -> bool {
+        self.resting
+    }
+
+    /// Asks for a rest until `resume_at`, e.g. after an action failed for
+    /// lack of energy.
+    pub fn start_resting(&mut self, level: usize) {
+        self.rest_until(level, self.resume_at);
+    }
+
+    /// Rests for the ticks we expect to need to go from `level` to `target`,
+    /// or to a full battery if `target` is beyond it.
+    pub fn rest_until(&mut self, level: usize, target: usize) {
+        let target = target.min(self.capacity);
+        let ticks = self.ticks_to_reach(level, target);
+        if ticks > 0 {
+            self.resting = true;
+            self.target = target;
+            self.ticks_left = ticks;
+        }
+    }
+
+    /// Updates the regeneration estimate with the energy gained since the end
+    /// of the last tick.
+    pub fn observe_tick_start(&mut self, level: usize) {
+        if let Some(last) = self.last_level {
+            if level > last {
+                // smooth it out, a single tick can be off
+                self.regen_per_tick = 0.8 * self.regen_per_tick + 0.2 * (level - last) as f32;
+            }
+        }
+    }
+
+    pub fn observe_tick_end(&mut self, level: usize) {
+        self.last_level = Some(level);
+    }
+
+    /// Whether this tick should be spent idling, a rest stops once its ticks
+    /// are spent or its target is reached.
+    pub fn should_rest(&mut self, level: usize) -> bool {
+        if !self.resting && level < self.rest_below {
+            self.start_resting(level);
+        }
+        if self.resting {
+            if self.ticks_left == 0 || level >= self.target {
+                self.resting = false;
+            } else {
+                self.ticks_left -= 1;
+            }
+        }
+        self.resting
+    }
+
+    /// Estimated ticks of rest to go from `level` to `target`.
+    pub fn ticks_to_reach(&self, level: usize, target: usize) -> usize {
+        if level >= target {
+            return 0;
+        }
+        if self.regen_per_tick <= 0.0 {
+            return usize::MAX;
+        }
+        ((target - level) as f32 / self.regen_per_tick).ceil() as usize
+    }
+}
+
+impl Scrapbot {
+    /// Called at the start of every tick, returns true if the tick should be
+    /// spent resting (no actions, the world recharges us).
+    pub(crate) fn plan_recharge(&mut self) -> bool {
+        let level = self.get_energy().get_energy_level();
+        self.recharge.observe_tick_start(level);
+        let rest = self.recharge.should_rest(level);
+        if rest {
+            self.metrics.ticks_resting += 1;
+        }
+        rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rest_lasts_as_long_as_the_estimate() {
+        let mut planner = RechargePlanner {
+            regen_per_tick: 25.0,
+            ..Default::default()
+        };
+        planner.rest_until(400, 500);
+        let rested = (0..10).take_while(|_| planner.should_rest(400)).count();
+        assert_eq!(rested, 4);
+    }
+
+    #[test]
+    fn rest_stops_at_a_full_battery() {
+        let mut planner = RechargePlanner::default();
+        planner.rest_until(900, 5000);
+        let rested = (0..1000).take_while(|_| planner.should_rest(900)).count();
+        assert_eq!(rested, 10);
+    }
+
+    #[test]
+    fn rest_ends_once_the_target_is_reached() {
+        let mut planner = RechargePlanner::default();
+        planner.rest_until(0, 500);
+        assert!(planner.should_rest(0));
+        assert!(!planner.should_rest(500));
+    }
+
+    #[test]
+    fn running_low_rests_until_resume_at() {
+        let mut planner = RechargePlanner::default();
+        let expected = planner.ticks_to_reach(50, planner.resume_at);
+        let rested = (0..1000).take_while(|_| planner.should_rest(50)).count();
+        assert_eq!(rested, expected);
+    }
+}
@@ -0,0 +1,525 @@
+use std::mem::discriminant;
+
+use pmp_collect_all::CollectAll;
+use robot_for_visualizer::RobotForVisualizer;
+use robotics_lib::interface::{put, Direction};
+use robotics_lib::utils::LibError;
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{
+    blacklist::BlacklistReason,
+    collection::{has_room, same_kind, with_quantity},
+    grid::GridPos,
+    map_snapshot::tile_at,
+    routines::RoutineResult,
+    Scrapbot,
+};
+
+/// What to collect, how much we care about it and where it goes.
+#[derive(Debug, Clone)]
+pub struct ResourceRule {
+    /// Kind of content to pick up, the quantity is ignored.
+    pub content: Content,
+    /// Higher priorities are collected first.
+    pub priority: u8,
+    /// Kinds of content we can deposit into (Bin, Bank, Market, ...).
+    pub storage: Vec<Content>,
+    /// Storage tiles to prefer over the ones found while scanning.
+    pub storage_tiles: Vec<GridPos>,
+    /// Items we never deliver, they stay in the backpack.
+    pub keep: usize,
+}
+
+impl ResourceRule {
+    pub fn new(content: Content, priority: u8, storage: Vec<Content>) -> ResourceRule {
+        ResourceRule {
+            content,
+            priority,
+            storage,
+            storage_tiles: vec![],
+            keep: 0,
+        }
+    }
+
+    pub fn with_storage_tiles(mut self, tiles: Vec<GridPos>) -> ResourceRule {
+        self.storage_tiles = tiles;
+        self
+    }
+
+    pub fn with_keep(mut self, keep: usize) -> ResourceRule {
+        self.keep = keep;
+        self
+    }
+}
+
+/// Per-content priority table used by the routine.
+#[derive(Debug, Clone)]
+pub struct ResourceTable {
+    rules: Vec<ResourceRule>,
+}
+
+impl Default for ResourceTable {
+    fn default() -> Self {
+        Self::garbage_only()
+    }
+}
+
+impl ResourceTable {
+    pub fn new() -> ResourceTable {
+        ResourceTable { rules: vec![] }
+    }
+
+    /// The classic Scrapbot: garbage into bins and nothing else.
+    pub fn garbage_only() -> ResourceTable {
+        ResourceTable::new().with_rule(ResourceRule::new(
+            Content::Garbage(0),
+            10,
+            vec![Content::Bin(0..0)],
+        ))
+    }
+
+    /// Garbage first, then coins for the bank and rocks/wood for the market.
+    pub fn all_resources() -> ResourceTable {
+        ResourceTable::garbage_only()
+            .with_rule(ResourceRule::new(
+                Content::Coin(0),
+                8,
+                vec![Content::Bank(0..0)],
+            ))
+            .with_rule(ResourceRule::new(
+                Content::Rock(0),
+                5,
+                vec![Content::Market(0)],
+            ))
+            .with_rule(ResourceRule::new(
+                Content::Tree(0),
+                5,
+                vec![Content::Market(0)],
+            ))
+    }
+
+    /// Adds a rule, replacing any previous rule for the same kind of content.
+    pub fn with_rule(mut self, rule: ResourceRule) -> ResourceTable {
+        self.rules.retain(|r| !same_kind(&r.content, &rule.content));
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rule_for(&self, content: &Content) -> Option<&ResourceRule> {
+        self.rules.iter().find(|r| same_kind(&r.content, content))
+    }
+
+    /// Rules sorted from the highest priority.
+    pub fn by_priority(&self) -> Vec<ResourceRule> {
+        let mut rules = self.rules.clone();
+        rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+        rules
+    }
+}
+
+impl Scrapbot {
+    /// Scans around the robot for `rule.content` and stores the targets found,
+    /// nearest first. Piles are shared with the fleet.
+    pub(crate) fn lssf_search_resource(
+        &mut self,
+        world: &mut World,
+        rule: &ResourceRule,
+    ) -> Result<bool, LibError> {
+        self.lssf_update(world, None);
+        let mut found: Vec<GridPos> = self
+            .lssf
+            .as_ref()
+            .unwrap()
+            .get_content_vec(&rule.content)
+            .into_iter()
+            .map(GridPos::from_lssf)
+            .collect();
+
+        // LSSF only matches the default variant, add piles of any size we know
+        self.refresh_piles(world, &rule.content);
+        for (coords, q) in self.scan_known_content(world, &rule.content) {
+            if q > 0 && !found.contains(&coords) {
+                found.push(coords);
+            }
+        }
+        found.retain(|coords| self.piles.get(coords) != Some(&0));
+
+        // share with the other bots, then don't bring back piles that failed
+        // recently
+        self.sync_piles_with_fleet(&rule.content, &mut found);
+        self.blacklist.retain_allowed(&mut found, self.tick);
+
+        if found.is_empty() {
+            return Ok(false);
+        }
+
+        let mut coords = self
+            .resource_coords
+            .remove(&discriminant(&rule.content))
+            .unwrap_or_default();
+        coords.retain(|c| !found.contains(c));
+        coords.extend(found);
+        self.blacklist.retain_allowed(&mut coords, self.tick);
+        self.sort_from_nearest(world, &mut coords);
+        self.resource_coords
+            .insert(discriminant(&rule.content), coords);
+        Ok(true)
+    }
+
+    /// Storage tiles accepting `rule.content`, configured ones first and then
+    /// the known ones (ours and the fleet's), nearest first. Bins and banks we
+    /// know are full are left out.
+    pub(crate) fn storage_targets(&self, world: &World, rule: &ResourceRule) -> Vec<GridPos> {
+        let mut known = vec![];
+        for storage in &rule.storage {
+            for (coords, _) in self.scan_known_content(world, storage) {
+                if !known.iter().any(|(c, _)| *c == coords) {
+                    known.push((coords, storage.clone()));
+                }
+            }
+        }
+        self.sync_storages_with_fleet(rule, &mut known);
+
+        let mut known: Vec<GridPos> = known
+            .into_iter()
+            .map(|(coords, _)| coords)
+            .filter(|coords| !rule.storage_tiles.contains(coords))
+            .collect();
+        self.sort_from_nearest(world, &mut known);
+
+        let mut targets = rule.storage_tiles.clone();
+        targets.extend(known);
+        self.blacklist.retain_allowed(&mut targets, self.tick);
+        let known_map = self.known_map(world);
+        targets.retain(|coords| {
+            tile_at(&known_map, *coords).is_none_or(|tile| has_room(&tile.content))
+        });
+        targets
+    }
+
+    /// Collects `content` around the robot, up to what we can take. Returns
+    /// how many items ended up in the backpack.
+    pub(crate) fn collect_new_resource(
+        &mut self,
+        world: &mut World,
+        content: &Content,
+        range: usize,
+    ) -> Result<usize, LibError> {
+        let takeable = self.takeable_quantity(content);
+        if takeable == 0 {
+            return Ok(0);
+        }
+
+        // one requirement per pile size we know about, capped to what we can
+        // take so a big pile is only partially picked up when we're nearly
+        // full
+        let requirements = self.collection_requirements(world, content, takeable);
+        let before = self.carried_quantity(content);
+        CollectAll::collect_items(self, world, range, requirements);
+        self.refresh_piles(world, content);
+        Ok(self.carried_quantity(content).saturating_sub(before))
+    }
+
+    pub(crate) fn routine_collect_resource(
+        &mut self,
+        world: &mut World,
+        rule: &ResourceRule,
+    ) -> Result<RoutineResult, LibError> {
+        if !self.lssf_search_resource(world, rule)? {
+            return Ok(RoutineResult::NewResourcesNotFound);
+        }
+
+        let key = discriminant(&rule.content);
+        let targets = self.resource_coords.remove(&key).unwrap_or_default();
+        let mut bad_coords = vec![];
+        let mut gathered = 0;
+        let mut result = None;
+
+        for coords in &targets {
+            if self.takeable_quantity(&rule.content) == 0 {
+                result = Some(RoutineResult::FilledBackpack);
+                break;
+            }
+
+            if self.blacklist.is_banned(*coords, self.tick) {
+                bad_coords.push(*coords);
+                continue;
+            }
+
+            // another bot is already heading there
+            if !self.claim_target(*coords) {
+                continue;
+            }
+
+            if !self.populate_action_vec_given_point(world, *coords) {
+                self.blacklist
+                    .ban(*coords, BlacklistReason::Unreachable, self.tick);
+                self.release_target(*coords);
+                bad_coords.push(*coords);
+                continue;
+            }
+            // don't go further than we could come back from
+            if !self.can_afford_plan(world, *coords) {
+                self.release_target(*coords);
+                // too far for a full battery, try the next one
+                if self.blacklist.is_banned(*coords, self.tick) {
+                    bad_coords.push(*coords);
+                    continue;
+                }
+                result = Some(RoutineResult::LowEnergy);
+                break;
+            }
+
+            // keep room for this pile on the way, clearing obstacles must not
+            // fill it
+            let planned = self
+                .piles
+                .get(coords)
+                .copied()
+                .unwrap_or(0)
+                .min(self.takeable_quantity(&rule.content));
+            self.backpack_manager.reserve(&rule.content, planned);
+            let walked = self.run_action_vec(world);
+            self.backpack_manager.release(&rule.content, planned);
+            match walked {
+                Ok(()) => {}
+                // no safe way there after all, the next pile may have one
+                Err(LibError::CannotWalk) => {
+                    self.blacklist
+                        .ban(*coords, BlacklistReason::Unreachable, self.tick);
+                    self.release_target(*coords);
+                    bad_coords.push(*coords);
+                    continue;
+                }
+                Err(err) => {
+                    self.release_target(*coords);
+                    self.resource_coords.insert(key, targets);
+                    return Err(err);
+                }
+            }
+
+            let collected = self.collect_new_resource(world, &rule.content, 15);
+
+            // tell the fleet what's left and let someone else take it
+            if let Some(fleet) = &self.fleet {
+                let remaining = self.piles.get(coords).copied().unwrap_or(0);
+                fleet.publish_pile(*coords, with_quantity(&rule.content, remaining));
+            }
+            self.release_target(*coords);
+
+            match collected? {
+                0 => {
+                    self.blacklist
+                        .ban(*coords, BlacklistReason::EmptyTrash, self.tick);
+                    bad_coords.push(*coords);
+                }
+                q => gathered += q,
+            }
+        }
+
+        // Remove bad locations and the piles we emptied, partially collected
+        // piles stay so we can come back for the rest
+        let remaining: Vec<GridPos> = targets
+            .into_iter()
+            .filter(|coords| !bad_coords.contains(coords))
+            .filter(|coords| self.piles.get(coords) != Some(&0))
+            .collect();
+        self.resource_coords.insert(key, remaining);
+
+        match result {
+            Some(result) => Ok(result),
+            None if gathered == 0 => Ok(RoutineResult::NoChanges),
+            None if self.get_remaining_backpack_space()
+                < (self.backpack_capacity() as f32 / 6f32).floor() as usize =>
+            {
+                Ok(RoutineResult::PartiallyFilledBackpack)
+            }
+            None => Ok(RoutineResult::Success),
+        }
+    }
+
+    /// Items of `rule.content` we carry beyond what the rule keeps.
+    pub(crate) fn deliverable(&self, rule: &ResourceRule) -> usize {
+        self.carried_quantity(&rule.content)
+            .saturating_sub(rule.keep)
+    }
+
+    pub(crate) fn routine_deliver_resource(
+        &mut self,
+        world: &mut World,
+        rule: &ResourceRule,
+    ) -> Result<RoutineResult, LibError> {
+        self.lssf_update(world, None);
+
+        if self.deliverable(rule) == 0 {
+            return Ok(RoutineResult::EmptyBackpack);
+        }
+
+        let targets = self.storage_targets(world, rule);
+        if targets.is_empty() {
+            return Ok(RoutineResult::NewResourcesNotFound);
+        }
+
+        for coords in targets {
+            let direction = match self.approach_storage(world, coords)? {
+                Some(direction) => direction,
+                None => continue,
+            };
+
+            // markets pay for what they take and refuse what they don't buy
+            let selling = tile_at(&self.known_map(world), coords)
+                .is_some_and(|tile| matches!(tile.content, Content::Market(_)));
+            let coins_before = self.carried_quantity(&Content::Coin(0));
+            let quantity = self.deliverable(rule);
+            let dropped =
+                match self.drop_content_in_front_of(world, &rule.content, quantity, direction) {
+                    Ok(dropped) => dropped,
+                    // the market doesn't buy it, other errors (energy, ...) aren't
+                    // a refusal
+                    Err(LibError::OperationNotAllowed) | Err(LibError::WrongContentUsed)
+                        if selling =>
+                    {
+                        0
+                    }
+                    Err(err) => return Err(err),
+                };
+
+            match dropped {
+                0 if selling => {
+                    self.blacklist
+                        .ban(coords, BlacklistReason::RefusedSale, self.tick);
+                }
+                0 => {
+                    self.blacklist
+                        .ban(coords, BlacklistReason::FullBin, self.tick);
+                }
+                sold if selling => {
+                    self.metrics.items_sold += sold;
+                    self.metrics.coins_earned += self
+                        .carried_quantity(&Content::Coin(0))
+                        .saturating_sub(coins_before);
+                    return Ok(RoutineResult::Success);
+                }
+                _ => return Ok(RoutineResult::Success),
+            }
+        }
+
+        Ok(RoutineResult::NewResourcesNotFound)
+    }
+
+    /// Walks next to the storage at `coords` and returns the direction to
+    /// face it. Unreachable storages are banned and give `None`.
+    pub(crate) fn approach_storage(
+        &mut self,
+        world: &mut World,
+        coords: GridPos,
+    ) -> Result<Option<Direction>, LibError> {
+        match self.approach_adjacent(world, coords, true) {
+            Ok(direction) => Ok(Some(direction)),
+            Err(LibError::CannotWalk) => {
+                self.blacklist
+                    .ban(coords, BlacklistReason::Unreachable, self.tick);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Puts up to `quantity` items of the same kind as `content` in front of
+    /// us.
+    pub(crate) fn drop_content_in_front_of(
+        &mut self,
+        world: &mut World,
+        content: &Content,
+        quantity: usize,
+        direction: Direction,
+    ) -> Result<usize, LibError> {
+        let key = self
+            .robot
+            .backpack
+            .get_contents()
+            .iter()
+            .find(|(c, q)| same_kind(c, content) && **q > 0)
+            .map(|(c, q)| (c.clone(), quantity.min(*q)));
+
+        match key {
+            Some((content, quantity)) if quantity > 0 => {
+                let dropped = put(self, world, content, quantity, direction)?;
+                self.store_tiles(world);
+                Ok(dropped)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::world::tile::TileType;
+
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    #[test]
+    fn pile_behind_lava_is_banned_instead_of_retried() {
+        let pile = GridPos::new(4, 7);
+        let world = (0..9).fold(
+            TestWorld::grass(9, 9)
+                .spawn_at(GridPos::new(4, 4))
+                .with_content(pile, Content::Garbage(2)),
+            |world, row| world.with_tile_type(GridPos::new(row, 6), TileType::Lava),
+        );
+        world.run(test_bot(), move |bot, world| {
+            bot.lssf_update(world, Some(7));
+            let rule = ResourceTable::garbage_only().by_priority().remove(0);
+            let start = bot.position();
+
+            assert!(bot.routine_collect_resource(world, &rule).is_ok());
+            assert!(bot.blacklist.is_banned(pile, bot.tick));
+            assert_eq!(bot.position(), start);
+        });
+    }
+
+    #[test]
+    fn rules_come_by_priority_and_replace_their_kind() {
+        let table = ResourceTable::all_resources().with_rule(ResourceRule::new(
+            Content::Rock(3),
+            20,
+            vec![],
+        ));
+        let order: Vec<u8> = table
+            .by_priority()
+            .iter()
+            .map(|rule| rule.priority)
+            .collect();
+
+        assert_eq!(order, vec![20, 10, 8, 5]);
+        assert_eq!(table.by_priority().len(), 4);
+        assert!(table
+            .rule_for(&Content::Rock(0))
+            .unwrap()
+            .storage
+            .is_empty());
+        assert!(table.rule_for(&Content::Fish(0)).is_none());
+    }
+
+    #[test]
+    fn full_bins_are_not_storage_targets() {
+        let full = GridPos::new(4, 5);
+        let open = GridPos::new(4, 7);
+        TestWorld::grass(9, 9)
+            .spawn_at(GridPos::new(4, 4))
+            .with_content(full, Content::Bin(5..5))
+            .with_content(open, Content::Bin(0..5))
+            .run(test_bot(), move |bot, world| {
+                bot.lssf_update(world, Some(7));
+                let rule = ResourceTable::garbage_only().by_priority().remove(0);
+                assert_eq!(bot.storage_targets(world, &rule), vec![open]);
+
+                // not even when it's configured
+                let rule = rule.with_storage_tiles(vec![full]);
+                assert_eq!(bot.storage_targets(world, &rule), vec![open]);
+            });
+    }
+}
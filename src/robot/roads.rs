@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::mem::discriminant;
+
+use robotics_lib::utils::LibError;
+use robotics_lib::world::tile::{Content, TileType};
+use robotics_lib::world::World;
+
+use crate::robot::{
+    blacklist::{BlacklistReason, TargetBlacklist},
+    grid::GridPos,
+    map_snapshot::{tile_at, KnownMap},
+    routines::RoutineResult,
+    Scrapbot,
+};
+
+/// Tile type that `put`ting rocks turns into a road, and how many rocks it
+/// takes.
+#[derive(Debug, Clone)]
+pub struct RoadRule {
+    pub tile_type: TileType,
+    pub rocks: usize,
+}
+
+/// Remembers how often each tile is crossed on delivery trips and decides
+/// which tiles are worth paving with the rocks we carry.
+///
+/// A tile is paved only once it has been crossed `min_traffic` times and the
+/// energy saved by the next as many crossings beats the cost of the rocks
+/// (`rock_cost` each) plus `put_cost`.
+#[derive(Debug, Clone)]
+pub struct RoadPlanner {
+    pub enabled: bool,
+    pub rules: Vec<RoadRule>,
+    pub min_traffic: usize,
+    pub rock_cost: usize,
+    pub put_cost: usize,
+    traffic: HashMap<GridPos, usize>,
+    built: Vec<GridPos>,
+}
+
+impl Default for RoadPlanner {
+    fn default() -> Self {
+        RoadPlanner {
+            enabled: true,
+            // the library only paves shallow water, rocks put anywhere else
+            // stay on the tile as content and block it
+            rules: vec![RoadRule {
+                tile_type: TileType::ShallowWater,
+                rocks: 1,
+            }],
+            min_traffic: 3,
+            rock_cost: 3,
+            put_cost: 1,
+            traffic: HashMap::new(),
+            built: vec![],
+        }
+    }
+}
+
+impl RoadPlanner {
+    pub fn disabled() -> RoadPlanner {
+        RoadPlanner {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn rule_for(&self, tile_type: &TileType) -> Option<&RoadRule> {
+        self.rules
+            .iter()
+            .find(|rule| discriminant(&rule.tile_type) == discriminant(tile_type))
+    }
+
+    /// Counts a crossing of `coords` during a delivery trip.
+    pub fn record_crossing(&mut self, coords: GridPos) {
+        *self.traffic.entry(coords).or_insert(0) += 1;
+    }
+
+    pub fn get_traffic(&self, coords: GridPos) -> usize {
+        self.traffic.get(&coords).copied().unwrap_or(0)
+    }
+
+    /// Tiles we paved so far.
+    pub fn get_built(&self) -> &[GridPos] {
+        &self.built
+    }
+
+    /// Energy we expect to save by paving a tile minus what it costs us,
+    /// `None` if the tile can't be paved with the rocks we have.
+    pub fn net_savings(
+        &self,
+        known_map: &KnownMap,
+        coords: GridPos,
+        rocks: usize,
+    ) -> Option<isize> {
+        let tile = tile_at(known_map, coords)?;
+        let rule = self.rule_for(&tile.tile_type)?;
+        let traffic = self.get_traffic(coords);
+        if rule.rocks > rocks || traffic < self.min_traffic {
+            return None;
+        }
+
+        let per_crossing = tile
+            .tile_type
+            .properties()
+            .cost()
+            .saturating_sub(TileType::Street.properties().cost());
+        let savings = (per_crossing * traffic) as isize;
+        let cost = (rule.rocks * self.rock_cost + self.put_cost) as isize;
+        Some(savings - cost)
+    }
+
+    /// Busiest tile worth paving that isn't banned at `tick`, if any.
+    pub fn best_candidate(
+        &self,
+        known_map: &KnownMap,
+        rocks: usize,
+        blacklist: &TargetBlacklist,
+        tick: usize,
+    ) -> Option<GridPos> {
+        self.traffic
+            .keys()
+            .filter(|coords| !blacklist.is_banned(**coords, tick))
+            .filter_map(|coords| {
+                self.net_savings(known_map, *coords, rocks)
+                    .filter(|net| *net > 0)
+                    .map(|net| (*coords, net))
+            })
+            .max_by_key(|(coords, net)| (*net, *coords))
+            .map(|(coords, _)| coords)
+    }
+}
+
+impl Scrapbot {
+    /// Paves the busiest delivery tile with the rocks we carry, if it pays
+    /// off.
+    pub(crate) fn routine_build_road(
+        &mut self,
+        world: &mut World,
+    ) -> Result<RoutineResult, LibError> {
+        let rocks = self.carried_quantity(&Content::Rock(0));
+        if !self.roads.enabled || rocks == 0 {
+            return Ok(RoutineResult::NoChanges);
+        }
+
+        let known_map = self.known_map(world);
+        let target = match self
+            .roads
+            .best_candidate(&known_map, rocks, &self.blacklist, self.tick)
+        {
+            Some(target) => target,
+            None => return Ok(RoutineResult::NoChanges),
+        };
+        let needed = tile_at(&known_map, target)
+            .and_then(|tile| self.roads.rule_for(&tile.tile_type))
+            .map_or(0, |rule| rule.rocks);
+        drop(known_map);
+
+        // stop next to the tile and face it, try another one next time if
+        // we can't get there
+        let direction = match self.approach_adjacent(world, target, false) {
+            Ok(direction) => direction,
+            Err(LibError::CannotWalk) => {
+                self.blacklist
+                    .ban(target, BlacklistReason::Unreachable, self.tick);
+                return Ok(RoutineResult::NoChanges);
+            }
+            Err(err) => return Err(err),
+        };
+
+        let placed = self.drop_content_in_front_of(world, &Content::Rock(0), needed, direction)?;
+        if placed < needed {
+            return Ok(RoutineResult::NoChanges);
+        }
+        self.roads.built.push(target);
+        self.roads.traffic.remove(&target);
+        Ok(RoutineResult::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::world::tile::Tile;
+
+    use super::*;
+
+    // one row of shallow water with a grass tile at the end
+    fn known_map() -> KnownMap {
+        let tile = |tile_type| {
+            Some(Tile {
+                tile_type,
+                content: Content::None,
+                elevation: 0,
+            })
+        };
+        vec![vec![
+            tile(TileType::ShallowWater),
+            tile(TileType::ShallowWater),
+            tile(TileType::Grass),
+        ]]
+    }
+
+    fn per_crossing() -> usize {
+        TileType::ShallowWater.properties().cost() - TileType::Street.properties().cost()
+    }
+
+    fn cross(planner: &mut RoadPlanner, coords: GridPos, times: usize) {
+        for _ in 0..times {
+            planner.record_crossing(coords);
+        }
+    }
+
+    const WATER: GridPos = GridPos::new(0, 0);
+    const OTHER_WATER: GridPos = GridPos::new(0, 1);
+    const GRASS: GridPos = GridPos::new(0, 2);
+
+    #[test]
+    fn savings_need_a_rule_the_rocks_and_the_traffic() {
+        let map = known_map();
+        let mut planner = RoadPlanner::default();
+        cross(&mut planner, WATER, planner.min_traffic - 1);
+        cross(&mut planner, GRASS, planner.min_traffic);
+        assert_eq!(planner.net_savings(&map, WATER, 1), None);
+        assert_eq!(planner.net_savings(&map, GRASS, 1), None);
+
+        planner.record_crossing(WATER);
+        assert_eq!(planner.net_savings(&map, WATER, 0), None);
+        assert!(planner.net_savings(&map, WATER, 1).is_some());
+    }
+
+    #[test]
+    fn savings_pay_for_the_rocks_and_the_put() {
+        let map = known_map();
+        let mut planner = RoadPlanner::default();
+        cross(&mut planner, WATER, 5);
+        let cost = planner.rock_cost + planner.put_cost;
+        assert_eq!(
+            planner.net_savings(&map, WATER, 1),
+            Some((per_crossing() * 5) as isize - cost as isize)
+        );
+    }
+
+    #[test]
+    fn best_candidate_is_the_busiest_allowed_tile_that_pays_off() {
+        let map = known_map();
+        let mut planner = RoadPlanner {
+            rock_cost: 0,
+            // three crossings only pay the put back
+            put_cost: per_crossing() * 3,
+            ..Default::default()
+        };
+        let mut blacklist = TargetBlacklist::new();
+        cross(&mut planner, WATER, 3);
+        assert_eq!(planner.best_candidate(&map, 1, &blacklist, 0), None);
+
+        cross(&mut planner, OTHER_WATER, 4);
+        assert_eq!(
+            planner.best_candidate(&map, 1, &blacklist, 0),
+            Some(OTHER_WATER)
+        );
+
+        cross(&mut planner, WATER, 2);
+        assert_eq!(planner.best_candidate(&map, 1, &blacklist, 0), Some(WATER));
+        assert_eq!(planner.best_candidate(&map, 0, &blacklist, 0), None);
+
+        blacklist.ban(WATER, BlacklistReason::Unreachable, 0);
+        assert_eq!(
+            planner.best_candidate(&map, 1, &blacklist, 0),
+            Some(OTHER_WATER)
+        );
+    }
+}
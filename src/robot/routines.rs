@@ -1,9 +1,14 @@
+use robotics_lib::runner::Runnable;
 use robotics_lib::utils::LibError;
 use robotics_lib::world::tile::Content;
 use robotics_lib::world::World;
 
-use crate::robot::{BotAction, Scrapbot, MAX_BACKPACK_ITEMS};
+use crate::robot::{
+    blacklist::BlacklistReason, resources::ResourceRule, scheduling::ScheduleBias, BotAction,
+    Scrapbot,
+};
 
+#[derive(Debug)]
 pub(crate) enum RoutineResult {
     Success,
     FilledBackpack,
@@ -14,127 +19,34 @@ pub(crate) enum RoutineResult {
     FoundFullBin,
     EmptyTrashFound,
     Wandering,
+    LowEnergy,
 }
 
-impl Scrapbot {
-    pub(crate) fn routine_collect_trash(
-        &mut self,
-        world: &mut World,
-    ) -> Result<RoutineResult, LibError> {
-        self.full_recharge();
-        self.lssf_update(world, None);
-
-        if !self.lssf_search_trash(world)? {
-            return Ok(RoutineResult::NewResourcesNotFound);
-        }
-
-        let mut trash_gathered = 0;
-        let mut bad_trash_coords = vec![];
-        let trash_coords = self.trash_coords.take().unwrap();
-
-        for coords in &trash_coords {
-            if self.get_remaining_backpack_space() == 0 {
-                return Ok(RoutineResult::FilledBackpack);
-            }
-
-            self.populate_action_vec_given_point(world, *coords);
-            self.run_action_vec_and_then(world, BotAction::Walk)?;
-
-            match self.collect_new_trash_fill_backpack(world) {
-                Ok(q) => {
-                    if q == 0 {
-                        bad_trash_coords.push(*coords);
-                        continue;
-                    }
-                    trash_gathered += q;
-                }
-                Err(err) => {
-                    return Err(err);
-                }
-            }
-        }
-
-        // Remove bad trash locations
-        self.trash_coords = Some(
-            trash_coords
-                .into_iter()
-                .filter(|coords| !bad_trash_coords.contains(coords))
-                .collect(),
-        );
-
-        if trash_gathered == 0 {
-            return Ok(RoutineResult::NoChanges);
-        }
-
-        if self.get_remaining_backpack_space() < (MAX_BACKPACK_ITEMS as f32 / 6f32).floor() as usize
-        {
-            return Ok(RoutineResult::PartiallyFilledBackpack);
-        }
-
-        Ok(RoutineResult::Success)
-    }
-
-    pub(crate) fn routine_empty_trash(
-        &mut self,
-        world: &mut World,
-    ) -> Result<RoutineResult, LibError> {
-        self.full_recharge();
-        self.lssf_update(world, None);
-
-        if self.get_content_quantity(&Content::Garbage(0)) == 0 {
-            return Ok(RoutineResult::EmptyBackpack);
-        }
-
-        if !self.lssf_search_bins(world)? {
-            return Ok(RoutineResult::NewResourcesNotFound);
-        }
-
-        let bin_coords = self.bin_coords.take().unwrap_or_default();
-        let mut bad_bins_vec = vec![];
-
-        for coords in &bin_coords {
-            self.populate_action_vec_given_point(world, *coords);
-            self.run_action_vec_and_then(world, BotAction::Put)?;
-
-            let last_move_direction = self.get_last_move_direction();
-            if last_move_direction.is_none() {
-                return Err(LibError::OperationNotAllowed);
-            }
-
-            match self.drop_trash_into_bin_in_front_of(world, last_move_direction.unwrap()) {
-                Ok(0) => {
-                    bad_bins_vec.push(*coords);
-                }
-                Ok(999) => {
-                    return Ok(RoutineResult::EmptyBackpack);
-                }
-                Ok(q) => {
-                    return Ok(RoutineResult::Success);
-                }
-                Err(err) => {
-                    return Err(err);
-                }
-            }
+impl RoutineResult {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            RoutineResult::Success => "success",
+            RoutineResult::FilledBackpack => "filled_backpack",
+            RoutineResult::PartiallyFilledBackpack => "partially_filled_backpack",
+            RoutineResult::EmptyBackpack => "empty_backpack",
+            RoutineResult::NoChanges => "no_changes",
+            RoutineResult::NewResourcesNotFound => "new_resources_not_found",
+            RoutineResult::FoundFullBin => "found_full_bin",
+            RoutineResult::EmptyTrashFound => "empty_trash_found",
+            RoutineResult::Wandering => "wandering",
+            RoutineResult::LowEnergy => "low_energy",
         }
-
-        self.bin_coords = Some(
-            bin_coords
-                .into_iter()
-                .filter(|coords| !bad_bins_vec.contains(coords))
-                .collect(),
-        );
-
-        Ok(RoutineResult::NewResourcesNotFound)
     }
+}
 
+impl Scrapbot {
     pub(crate) fn routine_reach_closest_undiscovered_tile(
         &mut self,
         world: &mut World,
     ) -> Result<RoutineResult, LibError> {
-        self.full_recharge();
-
-        if self.move_away_from_border(world) {
-            self.move_to_center(world);
+        // stuck near the border is no reason to stop exploring
+        if self.move_away_from_border(world).unwrap_or(false) {
+            self.move_to_center(world)?;
             return Ok(RoutineResult::Success);
         }
 
@@ -142,9 +54,21 @@ impl Scrapbot {
         let next_location = self.find_closest_undiscovered_tile(world);
         match next_location {
             Some(location) => {
-                self.populate_action_vec_given_point(world, location);
-                self.run_action_vec_and_then(world, BotAction::Walk)
-                    .map(|_| RoutineResult::Success)
+                if !self.populate_action_vec_given_point(world, location) {
+                    self.blacklist
+                        .ban(location, BlacklistReason::Unreachable, self.tick);
+                    return Ok(RoutineResult::NewResourcesNotFound);
+                }
+                match self.run_action_vec(world) {
+                    Ok(()) => Ok(RoutineResult::Success),
+                    // no safe way there, look for another tile next time
+                    Err(LibError::CannotWalk) => {
+                        self.blacklist
+                            .ban(location, BlacklistReason::Unreachable, self.tick);
+                        Ok(RoutineResult::NewResourcesNotFound)
+                    }
+                    Err(err) => Err(err),
+                }
             }
             None => Ok(RoutineResult::NewResourcesNotFound),
         }
@@ -153,40 +77,133 @@ impl Scrapbot {
     pub(crate) fn routine(&mut self, world: &mut World) {
         // Initialize vectors if they are not set
         self.actions_vec.get_or_insert_with(Vec::new);
-        self.bin_coords.get_or_insert_with(Vec::new);
-        self.trash_coords.get_or_insert_with(Vec::new);
 
         if let BotAction::Start = self.bot_action {
             self.bot_action = BotAction::Walk;
-            self.move_away_from_border(world);
+            self.move_away_from_border(world).ok();
+        }
+
+        // moving is expensive right now, make the trip count
+        if self.should_deliver_early() {
+            self.handle_full_backpack(world);
+            return;
+        }
+
+        // what's left of the energy is for the way to a bin
+        if self.free_backpack_space() < self.backpack_capacity() && self.reserve_breached(world) {
+            self.handle_full_backpack(world);
+            return;
         }
 
         if self.get_remaining_backpack_space()
-            >= (MAX_BACKPACK_ITEMS as f32 * (0.6f32)).floor() as usize
+            < (self.backpack_capacity() as f32 * (0.6f32)).floor() as usize
         {
-            if let Ok(result) = self.routine_collect_trash(world) {
-                match result {
-                    RoutineResult::Success => self.handle_full_backpack(world),
-                    RoutineResult::NewResourcesNotFound => self.handle_wandering(world),
-                    RoutineResult::FilledBackpack => self.handle_full_backpack(world),
-                    _ => {}
+            self.handle_full_backpack(world);
+            return;
+        }
+
+        // try every resource from the highest priority, wander if none of
+        // them gave us something to do. Fires are looked at before the
+        // resources with a lower priority.
+        let mut fires_checked = !self.firefighting.enabled;
+        for rule in self.resources.by_priority() {
+            if !fires_checked && self.fires_come_before(&rule) {
+                fires_checked = true;
+                if self.handle_fires(world) {
+                    return;
                 }
             }
-        } else {
-            self.handle_full_backpack(world);
+            if self.handle_resource(world, &rule) {
+                return;
+            }
+        }
+        if !fires_checked && self.handle_fires(world) {
+            return;
+        }
+        self.handle_wandering(world);
+    }
+
+    fn record_routine(&mut self, routine: &'static str, result: &Result<RoutineResult, LibError>) {
+        let outcome = match result {
+            Ok(result) => result.as_str(),
+            Err(LibError::NotEnoughEnergy) => {
+                // wait for the energy to come back before trying again
+                let level = self.get_energy().get_energy_level();
+                self.recharge.start_resting(level);
+                "not_enough_energy"
+            }
+            Err(_) => "error",
+        };
+        self.metrics.record_routine(routine, outcome);
+    }
+
+    fn handle_resource(&mut self, world: &mut World, rule: &ResourceRule) -> bool {
+        let result = self.routine_collect_resource(world, rule);
+        self.record_routine("collect_resource", &result);
+        match result {
+            Ok(RoutineResult::Success)
+            | Ok(RoutineResult::FilledBackpack)
+            | Ok(RoutineResult::LowEnergy) => {
+                self.handle_full_backpack(world);
+                true
+            }
+            Ok(RoutineResult::NewResourcesNotFound) => false,
+            Ok(RoutineResult::NoChanges) => self.schedule_bias() != ScheduleBias::Explore,
+            _ => true,
         }
     }
 
+    fn handle_fires(&mut self, world: &mut World) -> bool {
+        let result = self.routine_fight_fire(world);
+        self.record_routine("fight_fire", &result);
+        // a failed fight leaves the tick to the other routines
+        !matches!(
+            result,
+            Ok(RoutineResult::NoChanges) | Ok(RoutineResult::NewResourcesNotFound) | Err(_)
+        )
+    }
+
     fn handle_wandering(&mut self, world: &mut World) {
-        if let Ok(RoutineResult::Success) = self.routine_reach_closest_undiscovered_tile(world) {}
+        // conditions are about to get worse, just look around instead of
+        // starting a long tour
+        if self.schedule_bias() == ScheduleBias::StayClose {
+            self.lssf_update(world, None);
+            return;
+        }
+        let result = self.routine_reach_closest_undiscovered_tile(world);
+        self.record_routine("reach_closest_undiscovered_tile", &result);
     }
 
     fn handle_full_backpack(&mut self, world: &mut World) {
-        match self.routine_empty_trash(world) {
-            Ok(RoutineResult::Success) => self.handle_wandering(world),
-            Ok(RoutineResult::EmptyBackpack) => self.handle_wandering(world),
-            Ok(RoutineResult::NewResourcesNotFound) => self.handle_wandering(world),
-            _ => {}
+        // pave the busy part of the way to the bins before the rocks go to
+        // storage
+        if self.carried_quantity(&Content::Rock(0)) > 0 {
+            let result = self.routine_build_road(world);
+            self.record_routine("build_road", &result);
+        }
+
+        if self.has_surplus() {
+            let result = self.routine_sell_surplus(world);
+            self.record_routine("sell_surplus", &result);
+        }
+
+        // go wandering afterwards unless a delivery got interrupted
+        let mut wander = true;
+        for rule in self.resources.by_priority() {
+            if self.carried_quantity(&rule.content) == 0 {
+                continue;
+            }
+            let result = self.routine_deliver_resource(world, &rule);
+            self.record_routine("deliver_resource", &result);
+            wander &= matches!(
+                result,
+                Ok(RoutineResult::Success)
+                    | Ok(RoutineResult::EmptyBackpack)
+                    | Ok(RoutineResult::NewResourcesNotFound)
+            );
+        }
+        if wander {
+            self.handle_wandering(world);
         }
     }
 }
@@ -0,0 +1,158 @@
+use crate::robot::grid::GridPos;
+
+/// Remembers where and how wide the last LSSF scan was, so that
+/// [`Scrapbot::lssf_update`] can skip scans that wouldn't tell us anything new.
+///
+/// A new scan is needed when the robot moved further than `move_threshold`
+/// tiles from the last scan center, when a wider area is requested, or when the
+/// known map changed (a tile content was updated) since the last scan.
+///
+/// LSSF plans its paths from the scan center, so the default threshold is 0:
+/// repeated scans from the same tile are skipped, any move triggers a new one.
+/// Raising it saves more scans but the planned paths start a few tiles off.
+/// [`ScanCache::disabled`] scans every time, to measure what the cache saves.
+///
+/// [`Scrapbot::lssf_update`]: crate::robot::Scrapbot::lssf_update
+#[derive(Debug, Clone)]
+pub struct ScanCache {
+    pub move_threshold: usize,
+    enabled: bool,
+    last_center: Option<GridPos>,
+    last_diameter: usize,
+    scanned_version: usize,
+    map_version: usize,
+}
+
+impl Default for ScanCache {
+    fn default() -> Self {
+        ScanCache::new(0)
+    }
+}
+
+impl ScanCache {
+    pub fn new(move_threshold: usize) -> ScanCache {
+        ScanCache {
+            move_threshold,
+            enabled: true,
+            last_center: None,
+            last_diameter: 0,
+            scanned_version: 0,
+            map_version: 0,
+        }
+    }
+
+    /// A cache that never skips a scan.
+    pub fn disabled() -> ScanCache {
+        ScanCache {
+            enabled: false,
+            ..ScanCache::new(0)
+        }
+    }
+
+    /// Marks the known map as changed, the next scan will run.
+    pub fn invalidate(&mut self) {
+        self.map_version += 1;
+    }
+
+    pub fn needs_scan(&self, center: GridPos, diameter: usize) -> bool {
+        match self.last_center {
+            _ if !self.enabled => true,
+            None => true,
+            Some(last_center) => {
+                self.map_version != self.scanned_version
+                    || diameter > self.last_diameter
+                    || last_center.manhattan(center) > self.move_threshold
+            }
+        }
+    }
+
+    pub fn record_scan(&mut self, center: GridPos, diameter: usize) {
+        self.last_center = Some(center);
+        self.last_diameter = diameter;
+        self.scanned_version = self.map_version;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::interface::{destroy, Direction};
+    use robotics_lib::world::tile::Content;
+
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    const CENTER: GridPos = GridPos { row: 4, col: 4 };
+
+    fn scanned(move_threshold: usize) -> ScanCache {
+        let mut cache = ScanCache::new(move_threshold);
+        cache.record_scan(CENTER, 5);
+        cache
+    }
+
+    #[test]
+    fn first_scan_is_always_needed() {
+        assert!(ScanCache::default().needs_scan(CENTER, 3));
+    }
+
+    #[test]
+    fn same_or_smaller_scan_at_the_same_center_is_skipped() {
+        let cache = scanned(0);
+        assert!(!cache.needs_scan(CENTER, 5));
+        assert!(!cache.needs_scan(CENTER, 3));
+        assert!(cache.needs_scan(CENTER, 7));
+    }
+
+    #[test]
+    fn moving_past_the_threshold_scans_again() {
+        let next = GridPos::new(4, 5);
+        assert!(scanned(0).needs_scan(next, 5));
+        assert!(!scanned(1).needs_scan(next, 5));
+        assert!(scanned(1).needs_scan(GridPos::new(5, 5), 5));
+    }
+
+    #[test]
+    fn invalidate_forces_the_next_scan_only() {
+        let mut cache = scanned(0);
+        cache.invalidate();
+        assert!(cache.needs_scan(CENTER, 5));
+        cache.record_scan(CENTER, 5);
+        assert!(!cache.needs_scan(CENTER, 5));
+    }
+
+    #[test]
+    fn disabled_cache_always_scans() {
+        let mut cache = ScanCache::disabled();
+        cache.record_scan(CENTER, 5);
+        assert!(cache.needs_scan(CENTER, 3));
+    }
+
+    #[test]
+    fn tile_content_update_invalidates_the_cache() {
+        TestWorld::grass(9, 9)
+            .spawn_at(CENTER)
+            .with_content(GridPos::new(4, 5), Content::Garbage(1))
+            .run(test_bot(), |bot, world| {
+                bot.lssf_update(world, Some(5));
+                assert!(!bot.scan_cache.needs_scan(CENTER, 5));
+                destroy(bot, world, Direction::Right).unwrap();
+                assert!(bot.scan_cache.needs_scan(CENTER, 5));
+            });
+    }
+
+    #[test]
+    fn cache_saves_repeated_scans_from_the_same_tile() {
+        for (cache, scans) in [(ScanCache::default(), 1), (ScanCache::disabled(), 4)] {
+            let mut bot = test_bot();
+            bot.scan_cache = cache;
+            TestWorld::grass(9, 9)
+                .spawn_at(CENTER)
+                .run(bot, move |bot, world| {
+                    for _ in 0..4 {
+                        bot.lssf_update(world, Some(5));
+                    }
+                    assert_eq!(bot.metrics.lssf_scans, scans);
+                    assert_eq!(bot.metrics.lssf_scans_skipped, 4 - scans);
+                });
+        }
+    }
+}
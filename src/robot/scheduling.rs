@@ -0,0 +1,252 @@
+use std::mem::{discriminant, Discriminant};
+
+use robotics_lib::world::environmental_conditions::{
+    DayTime, EnvironmentalConditions, WeatherType,
+};
+
+use crate::robot::Scrapbot;
+
+/// What the current conditions suggest the routine should focus on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleBias {
+    /// Sensing and walking are cheap, go discover new tiles.
+    Explore,
+    /// Moving is expensive, use the trip to empty the backpack.
+    Deliver,
+    /// Conditions are about to get worse, don't start long tours.
+    StayClose,
+    /// Nothing special, run the usual routine.
+    Neutral,
+}
+
+/// Follows the time of day across ticks to tell how long the current part
+/// of the day has left.
+///
+/// The library doesn't say how many ticks a part of the day lasts, so we
+/// count them: the first part we see is already under way and only tells us
+/// when the next one starts, the following ones give the length.
+#[derive(Debug, Clone, Default)]
+pub struct DaytimeClock {
+    current: Option<Discriminant<DayTime>>,
+    ticks_in_phase: usize,
+    full_phase: bool,
+    phase_length: Option<usize>,
+}
+
+impl DaytimeClock {
+    pub fn observe(&mut self, daytime: &DayTime) {
+        let daytime = discriminant(daytime);
+        match self.current {
+            Some(current) if current == daytime => self.ticks_in_phase += 1,
+            Some(_) => {
+                if self.full_phase {
+                    self.phase_length = Some(self.ticks_in_phase);
+                }
+                self.full_phase = true;
+                self.current = Some(daytime);
+                self.ticks_in_phase = 1;
+            }
+            None => {
+                self.current = Some(daytime);
+                self.ticks_in_phase = 1;
+            }
+        }
+    }
+
+    /// Ticks before the time of day changes, `None` until we've seen a whole
+    /// part of the day.
+    pub fn ticks_until_change(&self) -> Option<usize> {
+        self.phase_length
+            .map(|length| length.saturating_sub(self.ticks_in_phase))
+    }
+}
+
+/// Configurable policy that turns weather and time of day into a
+/// [`ScheduleBias`].
+///
+/// Every condition is mapped to a movement cost factor (1.0 = clear day), the
+/// thresholds decide which bias each factor produces.
+#[derive(Debug, Clone)]
+pub struct SchedulingPolicy {
+    pub enabled: bool,
+    pub sunny_factor: f32,
+    pub rainy_factor: f32,
+    pub foggy_factor: f32,
+    pub tropical_monsoon_factor: f32,
+    pub trentino_snow_factor: f32,
+    pub night_factor: f32,
+    /// At or below this factor we prefer exploring.
+    pub explore_max_factor: f32,
+    /// At or above this factor we prefer delivery trips.
+    pub deliver_min_factor: f32,
+    /// How much worse (ratio) the next time of day must be to avoid long tours.
+    pub worsening_ratio: f32,
+    /// Long tours are avoided only this many ticks before the change.
+    pub stay_close_window: usize,
+    /// Minimum share of the backpack that must be used before an early delivery.
+    pub early_delivery_fill: f32,
+    clock: DaytimeClock,
+}
+
+impl Default for SchedulingPolicy {
+    fn default() -> Self {
+        SchedulingPolicy {
+            enabled: true,
+            sunny_factor: 1.0,
+            rainy_factor: 1.2,
+            foggy_factor: 1.3,
+            tropical_monsoon_factor: 1.8,
+            trentino_snow_factor: 2.0,
+            night_factor: 1.5,
+            explore_max_factor: 1.0,
+            deliver_min_factor: 1.5,
+            worsening_ratio: 1.25,
+            stay_close_window: 20,
+            early_delivery_fill: 0.3,
+            clock: DaytimeClock::default(),
+        }
+    }
+}
+
+impl SchedulingPolicy {
+    pub fn disabled() -> SchedulingPolicy {
+        SchedulingPolicy {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Updates the time of day tracking, call it once per tick.
+    pub fn observe(&mut self, conditions: &EnvironmentalConditions) {
+        self.clock.observe(&conditions.get_time_of_day());
+    }
+
+    pub fn get_clock(&self) -> &DaytimeClock {
+        &self.clock
+    }
+
+    pub fn weather_factor(&self, weather: &WeatherType) -> f32 {
+        match weather {
+            WeatherType::Sunny => self.sunny_factor,
+            WeatherType::Rainy => self.rainy_factor,
+            WeatherType::Foggy => self.foggy_factor,
+            WeatherType::TropicalMonsoon => self.tropical_monsoon_factor,
+            WeatherType::TrentinoSnow => self.trentino_snow_factor,
+        }
+    }
+
+    pub fn daytime_factor(&self, daytime: &DayTime) -> f32 {
+        match daytime {
+            DayTime::Night => self.night_factor,
+            _ => 1.0,
+        }
+    }
+
+    /// Movement cost factor for the given conditions.
+    pub fn cost_factor(&self, conditions: &EnvironmentalConditions) -> f32 {
+        self.weather_factor(&conditions.get_weather_condition())
+            * self.daytime_factor(&conditions.get_time_of_day())
+    }
+
+    /// Cost factor once the current part of the day is over, assuming the
+    /// weather stays the same.
+    pub fn next_cost_factor(&self, conditions: &EnvironmentalConditions) -> f32 {
+        let next_daytime = match conditions.get_time_of_day() {
+            DayTime::Morning => DayTime::Afternoon,
+            DayTime::Afternoon => DayTime::Night,
+            DayTime::Night => DayTime::Morning,
+        };
+        self.weather_factor(&conditions.get_weather_condition())
+            * self.daytime_factor(&next_daytime)
+    }
+
+    pub fn bias(&self, conditions: &EnvironmentalConditions) -> ScheduleBias {
+        if !self.enabled {
+            return ScheduleBias::Neutral;
+        }
+
+        let now = self.cost_factor(conditions);
+        let next = self.next_cost_factor(conditions);
+        let change_is_close = self
+            .clock
+            .ticks_until_change()
+            .is_some_and(|ticks| ticks <= self.stay_close_window);
+
+        if now >= self.deliver_min_factor {
+            ScheduleBias::Deliver
+        } else if change_is_close && next >= now * self.worsening_ratio {
+            ScheduleBias::StayClose
+        } else if now <= self.explore_max_factor {
+            ScheduleBias::Explore
+        } else {
+            ScheduleBias::Neutral
+        }
+    }
+}
+
+impl Scrapbot {
+    /// Bias suggested by the last observed environmental conditions.
+    pub fn schedule_bias(&self) -> ScheduleBias {
+        match &self.environment {
+            Some(conditions) => self.scheduling.bias(conditions),
+            None => ScheduleBias::Neutral,
+        }
+    }
+
+    /// True when the conditions ask for a delivery and we carry enough
+    /// garbage to make the trip worth it.
+    pub(crate) fn should_deliver_early(&self) -> bool {
+        if self.schedule_bias() != ScheduleBias::Deliver {
+            return false;
+        }
+        let fill = self.backpack_fill_ratio();
+        fill > 0.0 && fill >= self.scheduling.early_delivery_fill
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observe(clock: &mut DaytimeClock, daytime: DayTime, ticks: usize) {
+        for _ in 0..ticks {
+            clock.observe(&daytime);
+        }
+    }
+
+    #[test]
+    fn partial_first_phase_tells_nothing() {
+        let mut clock = DaytimeClock::default();
+        observe(&mut clock, DayTime::Morning, 3);
+        assert_eq!(clock.ticks_until_change(), None);
+        // we joined the morning late, the afternoon is the first whole part
+        observe(&mut clock, DayTime::Afternoon, 8);
+        assert_eq!(clock.ticks_until_change(), None);
+    }
+
+    #[test]
+    fn phase_length_is_learned_from_the_first_whole_phase() {
+        let mut clock = DaytimeClock::default();
+        observe(&mut clock, DayTime::Morning, 3);
+        observe(&mut clock, DayTime::Afternoon, 6);
+        observe(&mut clock, DayTime::Night, 1);
+        assert_eq!(clock.ticks_until_change(), Some(5));
+    }
+
+    #[test]
+    fn ticks_until_change_counts_down_to_zero() {
+        let mut clock = DaytimeClock::default();
+        observe(&mut clock, DayTime::Morning, 1);
+        observe(&mut clock, DayTime::Afternoon, 6);
+        observe(&mut clock, DayTime::Night, 4);
+        assert_eq!(clock.ticks_until_change(), Some(2));
+        observe(&mut clock, DayTime::Night, 2);
+        assert_eq!(clock.ticks_until_change(), Some(0));
+        // a late change doesn't underflow
+        observe(&mut clock, DayTime::Night, 1);
+        assert_eq!(clock.ticks_until_change(), Some(0));
+        // and the longer night becomes the new length
+        observe(&mut clock, DayTime::Morning, 1);
+        assert_eq!(clock.ticks_until_change(), Some(6));
+    }
+}
@@ -0,0 +1,220 @@
+use std::cmp::max;
+
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{
+    collection::same_kind,
+    grid::GridPos,
+    map_snapshot::{known_tiles_around, KnownMap},
+    metrics::ScanYield,
+    Scrapbot,
+};
+
+/// Adapts the radius of the default LSSF scans to what they bring back.
+///
+/// After `grow_after` scans in a row discovering fewer than `min_new_tiles`
+/// tiles the radius grows by `step`. A scan showing at least `dense_targets`
+/// garbage piles shrinks it: there's enough to do nearby, wide scans would
+/// only cost time. The radius never goes past `max_radius` or a quarter of
+/// the map, whichever is larger, so big maps can scan wider than the fixed
+/// default. The current radius lives in [`Scrapbot::search_radius`].
+///
+/// Scans cut short by the map border don't say much about the radius and
+/// leave it alone.
+#[derive(Debug, Clone)]
+pub struct SensingPolicy {
+    pub enabled: bool,
+    pub min_radius: usize,
+    pub max_radius: usize,
+    pub step: usize,
+    pub grow_after: usize,
+    pub min_new_tiles: usize,
+    pub dense_targets: usize,
+    barren_scans: usize,
+}
+
+impl Default for SensingPolicy {
+    fn default() -> Self {
+        SensingPolicy {
+            enabled: true,
+            min_radius: 2,
+            max_radius: 16,
+            step: 2,
+            grow_after: 2,
+            min_new_tiles: 5,
+            dense_targets: 4,
+            barren_scans: 0,
+        }
+    }
+}
+
+impl SensingPolicy {
+    /// Always scans with the fixed default diameter, a quarter of the map.
+    pub fn disabled() -> SensingPolicy {
+        SensingPolicy {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Largest radius on a `rows` x `cols` map.
+    pub fn radius_cap(&self, rows: usize, cols: usize) -> usize {
+        max(self.max_radius, max(rows, cols) / 4).max(self.min_radius)
+    }
+
+    /// Radius of the first scan, the same as the fixed default.
+    pub fn initial_radius(&self, rows: usize, cols: usize) -> usize {
+        (max(rows, cols) / 8).clamp(self.min_radius, self.radius_cap(rows, cols))
+    }
+
+    /// Radius for the next scan, given what the last one found, never above
+    /// `cap`.
+    pub fn next_radius(
+        &mut self,
+        radius: usize,
+        cap: usize,
+        new_tiles: usize,
+        targets: usize,
+    ) -> usize {
+        if targets >= self.dense_targets {
+            self.barren_scans = 0;
+            return radius.saturating_sub(self.step).max(self.min_radius);
+        }
+        if new_tiles >= self.min_new_tiles {
+            self.barren_scans = 0;
+            return radius;
+        }
+        self.barren_scans += 1;
+        if self.barren_scans < self.grow_after {
+            return radius;
+        }
+        self.barren_scans = 0;
+        (radius + self.step).min(cap)
+    }
+}
+
+/// Garbage piles in the square of side `diameter` centred on `center`.
+fn targets_around(known_map: &KnownMap, center: GridPos, diameter: usize) -> usize {
+    let radius = diameter / 2;
+    known_map
+        .iter()
+        .enumerate()
+        .skip(center.row.saturating_sub(radius))
+        .take(diameter)
+        .flat_map(|(row, tiles)| {
+            tiles
+                .iter()
+                .enumerate()
+                .filter_map(move |(col, tile)| Some((GridPos::new(row, col), tile.as_ref()?)))
+        })
+        .filter(|(pos, tile)| {
+            pos.chebyshev(center) <= radius && same_kind(&tile.content, &Content::Garbage(0))
+        })
+        .count()
+}
+
+impl Scrapbot {
+    /// Diameter of the next default scan on a `rows` x `cols` map.
+    pub(crate) fn default_scan_diameter(&mut self, rows: usize, cols: usize) -> usize {
+        if !self.sensing.enabled {
+            return max(rows, cols) / 4;
+        }
+        let initial = self.sensing.initial_radius(rows, cols);
+        let radius = *self.search_radius.get_or_insert(initial);
+        radius * 2 + 1
+    }
+
+    /// Records what a scan discovered and, for default scans, adapts the
+    /// radius of the next one. `known_before` is how many tiles of the
+    /// scanned area we knew before the scan.
+    pub(crate) fn record_scan_yield(
+        &mut self,
+        world: &World,
+        center: GridPos,
+        diameter: usize,
+        known_before: usize,
+        adapt: bool,
+    ) {
+        let known_map = self.known_map(world);
+        let new_tiles =
+            known_tiles_around(&known_map, center, diameter).saturating_sub(known_before);
+        self.map_snapshot.borrow_mut().add_known(new_tiles);
+        let targets = targets_around(&known_map, center, diameter);
+        self.metrics.record_scan(ScanYield {
+            tick: self.tick,
+            diameter,
+            new_tiles,
+            targets,
+        });
+
+        if adapt && self.sensing.enabled {
+            if let Some(radius) = self.search_radius {
+                let (rows, cols) = self.world_dims(world);
+                let cap = self.sensing.radius_cap(rows, cols);
+                self.search_radius =
+                    Some(self.sensing.next_radius(radius, cap, new_tiles, targets));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    // 40x40 grass with four piles around `center`, enough to shrink the
+    // radius of a scan that sees them
+    fn piles_around(center: GridPos) -> TestWorld {
+        [(-1, -1), (-1, 1), (1, -1), (1, 1)].into_iter().fold(
+            TestWorld::grass(40, 40).spawn_at(center),
+            |world, (dr, dc)| {
+                let pile = GridPos::new(
+                    (center.row as i64 + dr) as usize,
+                    (center.col as i64 + dc) as usize,
+                );
+                world.with_content(pile, Content::Garbage(1))
+            },
+        )
+    }
+
+    #[test]
+    fn big_maps_can_scan_wider_than_the_fixed_default() {
+        let sensing = SensingPolicy::default();
+        for size in [64, 128, 256, 512] {
+            let fixed_diameter = size / 4;
+            assert!(sensing.radius_cap(size, size) * 2 + 1 >= fixed_diameter);
+        }
+        assert_eq!(sensing.radius_cap(20, 20), sensing.max_radius);
+    }
+
+    #[test]
+    fn radius_grows_up_to_the_cap() {
+        let mut sensing = SensingPolicy::default();
+        let cap = sensing.radius_cap(256, 256);
+        let mut radius = sensing.initial_radius(256, 256);
+        for _ in 0..100 {
+            radius = sensing.next_radius(radius, cap, 0, 0);
+        }
+        assert_eq!(radius, cap);
+    }
+
+    #[test]
+    fn dense_scans_shrink_the_radius() {
+        piles_around(GridPos::new(20, 20)).run(test_bot(), |bot, world| {
+            bot.search_radius = Some(5);
+            bot.lssf_update(world, None);
+            assert_eq!(bot.search_radius, Some(5 - bot.sensing.step));
+        });
+    }
+
+    #[test]
+    fn scans_cut_by_the_border_keep_the_radius() {
+        piles_around(GridPos::new(1, 20)).run(test_bot(), |bot, world| {
+            bot.search_radius = Some(5);
+            bot.lssf_update(world, None);
+            assert_eq!(bot.search_radius, Some(5));
+        });
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+use robotics_lib::world::tile::TileType;
+use robotics_lib::world::World;
+use sense_and_find_by_rustafariani::Action;
+
+use crate::robot::{
+    cost_model::{walk_back, CostModel},
+    grid::{direction_to_action, GridPos},
+    map_snapshot::KnownMap,
+    Scrapbot,
+};
+
+/// Teleport tiles we've seen so far.
+///
+/// The library only lets us teleport between active pads (the ones we've
+/// already stepped on), inactive ones are kept to know where they are.
+#[derive(Debug, Clone)]
+pub struct TeleportNetwork {
+    pads: BTreeMap<GridPos, bool>,
+    /// Energy we expect a teleport to cost.
+    pub hop_cost: usize,
+    /// Trips shorter than this (in steps) are always walked.
+    pub min_trip_length: usize,
+}
+
+impl Default for TeleportNetwork {
+    fn default() -> Self {
+        TeleportNetwork {
+            pads: BTreeMap::new(),
+            hop_cost: 30,
+            min_trip_length: 20,
+        }
+    }
+}
+
+impl TeleportNetwork {
+    /// Adds every teleport tile of the known map, returns how many are new.
+    pub fn catalogue(&mut self, known_map: &KnownMap) -> usize {
+        let before = self.pads.len();
+        for (row, tiles) in known_map.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                if let Some(tile) = tile {
+                    if let TileType::Teleport(active) = tile.tile_type {
+                        self.pads.insert(GridPos::new(row, col), active);
+                    }
+                }
+            }
+        }
+        self.pads.len() - before
+    }
+
+    pub fn pads(&self) -> impl Iterator<Item = &GridPos> {
+        self.pads.keys()
+    }
+
+    pub fn active_pads(&self) -> Vec<GridPos> {
+        self.pads
+            .iter()
+            .filter(|(_, active)| **active)
+            .map(|(coords, _)| *coords)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pads.is_empty()
+    }
+
+    /// Cheapest trip from `from` to `to` using one hop between two active
+    /// pads, only if it's cheaper than `walk_cost` (the cost of the walking
+    /// plan). Both walks only use safe known tiles.
+    pub fn shortcut(
+        &self,
+        known_map: &KnownMap,
+        model: &CostModel,
+        from: GridPos,
+        to: GridPos,
+        walk_cost: usize,
+    ) -> Option<Vec<Action>> {
+        let active = self.active_pads();
+        if active.len() < 2 {
+            return None;
+        }
+
+        let (costs, parents) = model.dijkstra(known_map, from, false);
+        // cheapest pad to walk to
+        let entry = *active.iter().min_by_key(|pad| costs[pad.row][pad.col])?;
+        let to_entry = costs[entry.row][entry.col];
+        if to_entry == usize::MAX || to_entry + self.hop_cost >= walk_cost {
+            return None;
+        }
+
+        let mut best: Option<(usize, Vec<Action>)> = None;
+        for exit in active.iter().filter(|pad| **pad != entry) {
+            let (exit_costs, exit_parents) = model.dijkstra(known_map, *exit, false);
+            let from_exit = match exit_costs.get(to.row).and_then(|costs| costs.get(to.col)) {
+                Some(cost) if *cost != usize::MAX => *cost,
+                _ => continue,
+            };
+            let total = to_entry + self.hop_cost + from_exit;
+            if total < walk_cost && best.as_ref().is_none_or(|(cost, _)| total < *cost) {
+                let mut actions: Vec<Action> = walk_back(&parents, entry)
+                    .iter()
+                    .map(direction_to_action)
+                    .collect();
+                let (row, col) = exit.to_lssf();
+                actions.push(Action::Teleport(row, col));
+                actions.extend(walk_back(&exit_parents, to).iter().map(direction_to_action));
+                best = Some((total, actions));
+            }
+        }
+        best.map(|(_, actions)| actions)
+    }
+}
+
+impl Scrapbot {
+    /// Keeps the teleport network in sync with the known map.
+    pub(crate) fn catalogue_teleports(&mut self, world: &World) {
+        let known_map = self.known_map(world);
+        self.teleport_network.catalogue(&known_map);
+    }
+
+    /// Replaces a long walking plan with a teleport trip if it's cheaper.
+    pub(crate) fn use_teleport_shortcut(&mut self, world: &World, actions: &mut Vec<Action>) {
+        if actions.len() < self.teleport_network.min_trip_length
+            || actions
+                .iter()
+                .any(|action| matches!(action, Action::Teleport(..)))
+        {
+            return;
+        }
+        let walk_cost = match self.plan_energy(world, actions) {
+            Some(cost) => cost,
+            None => return,
+        };
+
+        let known_map = self.known_map(world);
+        let destination = self.actions_destination(actions);
+        if let Some(shortcut) = self.teleport_network.shortcut(
+            &known_map,
+            &self.cost_model(),
+            self.position(),
+            destination,
+            walk_cost,
+        ) {
+            *actions = shortcut;
+            self.metrics.teleport_shortcuts += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::interface::{go, Direction};
+
+    use super::*;
+    use crate::robot::test_world::{test_bot, TestWorld};
+
+    fn west(steps: usize) -> Vec<Action> {
+        (0..steps)
+            .map(|_| direction_to_action(&Direction::Left))
+            .collect()
+    }
+
+    // a 3x30 corridor with a pad at each end, we walk to the east end so the
+    // whole corridor is known and both pads are in the network
+    fn corridor(hop_cost: usize, mut script: impl FnMut(&mut Scrapbot, &mut World) + 'static) {
+        let mut bot = test_bot();
+        bot.teleport_network.hop_cost = hop_cost;
+        TestWorld::grass(3, 30)
+            .with_tile_type(GridPos::new(1, 2), TileType::Teleport(true))
+            .with_tile_type(GridPos::new(1, 27), TileType::Teleport(true))
+            .spawn_at(GridPos::new(1, 1))
+            .run(bot, move |bot, world| {
+                for _ in 0..28 {
+                    go(bot, world, Direction::Right).unwrap();
+                }
+                bot.catalogue_teleports(world);
+                assert_eq!(bot.teleport_network.active_pads().len(), 2);
+                script(bot, world);
+            });
+    }
+
+    #[test]
+    fn long_walk_goes_through_the_pads_when_cheaper() {
+        corridor(5, |bot, world| {
+            let mut actions = west(28);
+            bot.use_teleport_shortcut(world, &mut actions);
+
+            let (row, col) = GridPos::new(1, 2).to_lssf();
+            assert_eq!(bot.metrics.teleport_shortcuts, 1);
+            assert!(actions
+                .iter()
+                .any(|action| matches!(action, Action::Teleport(r, c) if (*r, *c) == (row, col))));
+            assert_eq!(actions.len(), 4);
+            assert_eq!(bot.actions_destination(&actions), GridPos::new(1, 1));
+
+            bot.run_plan(world, west(28), false).unwrap();
+            assert_eq!(bot.position(), GridPos::new(1, 1));
+            assert_eq!(bot.metrics.failed_moves, 0);
+        });
+    }
+
+    #[test]
+    fn long_walk_is_kept_when_the_hop_costs_more() {
+        corridor(30, |bot, world| {
+            let mut actions = west(28);
+            bot.use_teleport_shortcut(world, &mut actions);
+
+            assert_eq!(bot.metrics.teleport_shortcuts, 0);
+            assert_eq!(actions.len(), 28);
+            assert!(!actions
+                .iter()
+                .any(|action| matches!(action, Action::Teleport(..))));
+
+            bot.run_plan(world, west(28), false).unwrap();
+            assert_eq!(bot.position(), GridPos::new(1, 1));
+        });
+    }
+}
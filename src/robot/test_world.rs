@@ -0,0 +1,142 @@
+//! Hand-built worlds for the tests, so a scenario can be set up tile by tile
+//! and run through a real `Runner`.
+
+use std::collections::HashMap;
+
+use robotics_lib::energy::Energy;
+use robotics_lib::event::events::Event;
+use robotics_lib::runner::backpack::BackPack;
+use robotics_lib::runner::{Runnable, Runner};
+use robotics_lib::world::coordinates::Coordinate;
+use robotics_lib::world::environmental_conditions::{EnvironmentalConditions, WeatherType};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+use robotics_lib::world::world_generator::Generator;
+use robotics_lib::world::World;
+
+use crate::robot::{grid::GridPos, Scrapbot};
+
+/// A flat grass map with whatever the test puts on it, sunny at noon unless
+/// the test says otherwise.
+pub(crate) struct TestWorld {
+    tiles: Vec<Vec<Tile>>,
+    spawn: GridPos,
+    weather: WeatherType,
+    hour: u8,
+}
+
+impl TestWorld {
+    pub(crate) fn grass(rows: usize, cols: usize) -> TestWorld {
+        let tile = Tile {
+            tile_type: TileType::Grass,
+            content: Content::None,
+            elevation: 0,
+        };
+        TestWorld {
+            tiles: vec![vec![tile; cols]; rows],
+            spawn: GridPos::new(0, 0),
+            weather: WeatherType::Sunny,
+            hour: 12,
+        }
+    }
+
+    pub(crate) fn with_tile_type(mut self, pos: GridPos, tile_type: TileType) -> TestWorld {
+        self.tiles[pos.row][pos.col].tile_type = tile_type;
+        self
+    }
+
+    pub(crate) fn with_elevation(mut self, pos: GridPos, elevation: usize) -> TestWorld {
+        self.tiles[pos.row][pos.col].elevation = elevation;
+        self
+    }
+
+    pub(crate) fn with_content(mut self, pos: GridPos, content: Content) -> TestWorld {
+        self.tiles[pos.row][pos.col].content = content;
+        self
+    }
+
+    pub(crate) fn with_weather(mut self, weather: WeatherType) -> TestWorld {
+        self.weather = weather;
+        self
+    }
+
+    pub(crate) fn at_hour(mut self, hour: u8) -> TestWorld {
+        self.hour = hour;
+        self
+    }
+
+    pub(crate) fn spawn_at(mut self, pos: GridPos) -> TestWorld {
+        self.spawn = pos;
+        self
+    }
+
+    /// Runs a single tick on this world where `script` drives `bot` instead
+    /// of its routine.
+    pub(crate) fn run(
+        mut self,
+        bot: Scrapbot,
+        script: impl FnMut(&mut Scrapbot, &mut World) + 'static,
+    ) {
+        let probe = Probe { bot, script };
+        let mut runner = Runner::new(Box::new(probe), &mut self).unwrap();
+        runner.game_tick().unwrap();
+    }
+}
+
+impl Generator for TestWorld {
+    fn gen(
+        &mut self,
+    ) -> (
+        Vec<Vec<Tile>>,
+        (usize, usize),
+        EnvironmentalConditions,
+        f32,
+        Option<HashMap<Content, f32>>,
+    ) {
+        let conditions = EnvironmentalConditions::new(&[self.weather], 15, self.hour).unwrap();
+        (
+            self.tiles.clone(),
+            (self.spawn.row, self.spawn.col),
+            conditions,
+            100.0,
+            None,
+        )
+    }
+}
+
+/// A bot without audio.
+pub(crate) fn test_bot() -> Scrapbot {
+    Scrapbot::headless()
+}
+
+// hands the world of a tick to the script, everything else goes to the bot
+struct Probe<F> {
+    bot: Scrapbot,
+    script: F,
+}
+
+impl<F: FnMut(&mut Scrapbot, &mut World)> Runnable for Probe<F> {
+    fn process_tick(&mut self, world: &mut World) {
+        (self.script)(&mut self.bot, world);
+    }
+    fn handle_event(&mut self, event: Event) {
+        self.bot.handle_event(event);
+    }
+    fn get_energy(&self) -> &Energy {
+        self.bot.get_energy()
+    }
+    fn get_energy_mut(&mut self) -> &mut Energy {
+        self.bot.get_energy_mut()
+    }
+    fn get_coordinate(&self) -> &Coordinate {
+        self.bot.get_coordinate()
+    }
+    fn get_coordinate_mut(&mut self) -> &mut Coordinate {
+        self.bot.get_coordinate_mut()
+    }
+    fn get_backpack(&self) -> &BackPack {
+        self.bot.get_backpack()
+    }
+    fn get_backpack_mut(&mut self) -> &mut BackPack {
+        self.bot.get_backpack_mut()
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::mem::{discriminant, Discriminant};
+
+use robotics_lib::utils::LibError;
+use robotics_lib::world::tile::Content;
+use robotics_lib::world::World;
+
+use crate::robot::{resources::ResourceRule, routines::RoutineResult, Scrapbot};
+
+/// What we sell at markets and how much of it we keep for ourselves.
+#[derive(Debug, Clone)]
+pub struct TradingPolicy {
+    pub enabled: bool,
+    /// Kinds of content we're happy to sell.
+    pub goods: Vec<Content>,
+    keep: HashMap<Discriminant<Content>, usize>,
+}
+
+impl Default for TradingPolicy {
+    fn default() -> Self {
+        let mut keep = HashMap::new();
+        // a few rocks are worth more as roads
+        keep.insert(discriminant(&Content::Rock(0)), 2);
+        TradingPolicy {
+            enabled: true,
+            goods: vec![Content::Rock(0), Content::Tree(0), Content::Fish(0)],
+            keep,
+        }
+    }
+}
+
+impl TradingPolicy {
+    pub fn disabled() -> TradingPolicy {
+        TradingPolicy {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Never sell the last `quantity` items of the same kind as `content`.
+    pub fn with_keep(mut self, content: &Content, quantity: usize) -> TradingPolicy {
+        self.keep.insert(discriminant(content), quantity);
+        self
+    }
+
+    pub fn get_keep(&self, content: &Content) -> usize {
+        self.keep.get(&discriminant(content)).copied().unwrap_or(0)
+    }
+}
+
+impl Scrapbot {
+    /// Items of the same kind as `content` we can sell right now.
+    pub fn surplus(&self, content: &Content) -> usize {
+        if !self.trading.enabled {
+            return 0;
+        }
+        self.carried_quantity(content)
+            .saturating_sub(self.trading.get_keep(content))
+    }
+
+    pub fn has_surplus(&self) -> bool {
+        self.trading
+            .goods
+            .iter()
+            .any(|content| self.surplus(content) > 0)
+    }
+
+    /// One delivery rule per good, markets as storage and the kept items
+    /// left out.
+    pub fn market_rules(&self) -> Vec<ResourceRule> {
+        self.trading
+            .goods
+            .iter()
+            .map(|good| {
+                ResourceRule::new(good.clone(), 0, vec![Content::Market(0)])
+                    .with_keep(self.trading.get_keep(good))
+            })
+            .collect()
+    }
+
+    /// Brings the surplus of every good to the nearest market buying it,
+    /// through `routine_deliver_resource`.
+    pub(crate) fn routine_sell_surplus(
+        &mut self,
+        world: &mut World,
+    ) -> Result<RoutineResult, LibError> {
+        if !self.has_surplus() {
+            return Ok(RoutineResult::EmptyBackpack);
+        }
+
+        let mut result = RoutineResult::NewResourcesNotFound;
+        for rule in self.market_rules() {
+            if self.deliverable(&rule) == 0 {
+                continue;
+            }
+            if let RoutineResult::Success = self.routine_deliver_resource(world, &rule)? {
+                result = RoutineResult::Success;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use robotics_lib::interface::{destroy, go, Direction};
+    use robotics_lib::runner::Runnable;
+
+    use super::*;
+    use crate::robot::{
+        blacklist::BlacklistReason,
+        grid::GridPos,
+        test_world::{test_bot, TestWorld},
+    };
+
+    const MARKET: GridPos = GridPos { row: 4, col: 3 };
+
+    // we start between a market and a pile of three items
+    fn market_world(pile: Content) -> TestWorld {
+        TestWorld::grass(9, 9)
+            .spawn_at(GridPos::new(4, 4))
+            .with_content(GridPos::new(4, 5), pile)
+            .with_content(MARKET, Content::Market(5))
+    }
+
+    fn pick_up(bot: &mut Scrapbot, world: &mut World) {
+        destroy(bot, world, Direction::Right).unwrap();
+    }
+
+    #[test]
+    fn surplus_leaves_the_kept_items_out() {
+        market_world(Content::Rock(3)).run(test_bot(), |bot, world| {
+            pick_up(bot, world);
+            // two rocks are kept by default
+            assert_eq!(bot.surplus(&Content::Rock(0)), 1);
+            assert!(bot.has_surplus());
+
+            bot.trading = TradingPolicy::default().with_keep(&Content::Rock(0), 3);
+            assert!(!bot.has_surplus());
+            bot.trading = TradingPolicy::disabled();
+            assert_eq!(bot.surplus(&Content::Rock(0)), 0);
+        });
+    }
+
+    #[test]
+    fn surplus_is_sold_at_the_market() {
+        market_world(Content::Rock(3)).run(test_bot(), |bot, world| {
+            pick_up(bot, world);
+            assert!(matches!(
+                bot.routine_sell_surplus(world),
+                Ok(RoutineResult::Success)
+            ));
+            assert_eq!(bot.metrics.items_sold, 1);
+            assert_eq!(bot.carried_quantity(&Content::Rock(0)), 2);
+            assert!(matches!(
+                bot.routine_sell_surplus(world),
+                Ok(RoutineResult::EmptyBackpack)
+            ));
+        });
+    }
+
+    #[test]
+    fn market_refusing_a_good_is_banned() {
+        let mut bot = test_bot();
+        bot.trading.goods = vec![Content::Garbage(0)];
+        market_world(Content::Garbage(3)).run(bot, |bot, world| {
+            pick_up(bot, world);
+            assert!(matches!(
+                bot.routine_sell_surplus(world),
+                Ok(RoutineResult::NewResourcesNotFound)
+            ));
+            let entry = bot.blacklist.get_entry(MARKET, bot.tick).unwrap();
+            assert_eq!(entry.reason, BlacklistReason::RefusedSale);
+            assert_eq!(bot.carried_quantity(&Content::Garbage(0)), 3);
+        });
+    }
+
+    #[test]
+    fn running_out_of_energy_is_not_a_refusal() {
+        market_world(Content::Rock(3)).run(test_bot(), |bot, world| {
+            pick_up(bot, world);
+            // pace up and down until the battery is flat
+            let mut up = true;
+            while go(bot, world, if up { Direction::Up } else { Direction::Down }).is_ok() {
+                up = !up;
+            }
+            assert!(bot.get_energy().get_energy_level() < 10);
+
+            assert!(bot.routine_sell_surplus(world).is_err());
+            assert!(!bot.blacklist.is_banned(MARKET, bot.tick));
+            assert_eq!(bot.metrics.items_sold, 0);
+        });
+    }
+}